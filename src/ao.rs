@@ -0,0 +1,101 @@
+use nalgebra_glm as glm;
+use rand::Rng;
+
+use crate::cube::Cube;
+use crate::ray_intersect::Intersect;
+use crate::render::{RenderContext, cosine_sample_hemisphere, find_containing, occluded};
+
+// Oclusión ambiental: qué fracción del término ambiente llega a un punto (1 = nada lo tapa)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AoMode {
+    Off,
+    // rayos en el hemisferio de la normal (ponderados por el coseno) de largo `radius`
+    Hemisphere { rays: u32, radius: f32 },
+    // ocupación de las celdas vecinas, como la iluminación suave de Minecraft
+    Voxel,
+}
+
+impl AoMode {
+    pub const DEFAULT_RAYS: u32 = 8;
+    pub const DEFAULT_RADIUS: f32 = 1.0;
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AoMode::Off => "off",
+            AoMode::Hemisphere { .. } => "hemisphere",
+            AoMode::Voxel => "voxel",
+        }
+    }
+
+    pub fn from_name(name: &str, rays: u32, radius: f32) -> Option<AoMode> {
+        match name {
+            "off" | "none" => Some(AoMode::Off),
+            "hemisphere" => Some(AoMode::Hemisphere { rays: rays.max(1), radius }),
+            "voxel" => Some(AoMode::Voxel),
+            _ => None,
+        }
+    }
+}
+
+// Brillo de una esquina según cuántos vecinos la tapan (3 = ninguno)
+const VOXEL_AO_LEVELS: [f32; 4] = [0.45, 0.65, 0.82, 1.0];
+
+// Solo los bloques opacos tapan el ambiente
+fn blocks_ambient(c: &Cube) -> bool {
+    c.material.transparency <= 0.01
+}
+
+pub fn ambient_occlusion(ctx: &RenderContext, isect: &Intersect) -> f32 {
+    match ctx.ao {
+        AoMode::Off => 1.0,
+        AoMode::Hemisphere { rays, radius } => hemisphere_ao(ctx, &isect.point, &isect.normal, rays, radius),
+        AoMode::Voxel => voxel_ao(ctx, &isect.point, &isect.normal),
+    }
+}
+
+fn hemisphere_ao(ctx: &RenderContext, point: &glm::Vec3, normal: &glm::Vec3, rays: u32, radius: f32) -> f32 {
+    let origin = point + normal * 1e-3;
+    let mut rng = rand::rng();
+    let hits = (0..rays)
+        .filter(|_| {
+            let dir = cosine_sample_hemisphere(normal, rng.random::<f32>(), rng.random::<f32>());
+            occluded(ctx, &origin, &dir, radius, blocks_ambient)
+        })
+        .count();
+    1.0 - hits as f32 / rays.max(1) as f32
+}
+
+// Suavizado por vértices: cada esquina de la cara mira los dos vecinos laterales y el de la
+// diagonal en la capa de celdas de enfrente; el valor se interpola en la cara.
+fn voxel_ao(ctx: &RenderContext, point: &glm::Vec3, normal: &glm::Vec3) -> f32 {
+    let Some(cube) = find_containing(ctx, &(point - normal * 1e-3), blocks_ambient) else { return 1.0 };
+    let axis = normal.iamax();
+    let (t1, t2) = ((axis + 1) % 3, (axis + 2) % 3);
+    let size = cube.half_size * 2.0;
+    let unit = |a: usize, len: f32| {
+        let mut v = glm::vec3(0.0, 0.0, 0.0);
+        v[a] = len;
+        v
+    };
+    // centro de la celda frente a la cara
+    let front = cube.center + unit(axis, size[axis] * normal[axis].signum());
+    // ocupación de las 8 celdas alrededor de la de enfrente (índices -1..1 desplazados a 0..2)
+    let mut occupied = [[0u32; 3]; 3];
+    for (i, row) in occupied.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            if i == 1 && j == 1 { continue; }
+            let q = front + unit(t1, (i as f32 - 1.0) * size[t1]) + unit(t2, (j as f32 - 1.0) * size[t2]);
+            *cell = find_containing(ctx, &q, blocks_ambient).is_some() as u32;
+        }
+    }
+    let corner = |i: usize, j: usize| {
+        let (side1, side2, diag) = (occupied[i][1], occupied[1][j], occupied[i][j]);
+        let level = if side1 + side2 == 2 { 0 } else { 3 - (side1 + side2 + diag) };
+        VOXEL_AO_LEVELS[level as usize]
+    };
+    let a = ((point[t1] - cube.center[t1]) / cube.half_size[t1]).clamp(-1.0, 1.0) * 0.5 + 0.5;
+    let b = ((point[t2] - cube.center[t2]) / cube.half_size[t2]).clamp(-1.0, 1.0) * 0.5 + 0.5;
+    let low = corner(0, 0) * (1.0 - a) + corner(2, 0) * a;
+    let high = corner(0, 2) * (1.0 - a) + corner(2, 2) * a;
+    low * (1.0 - b) + high * b
+}
@@ -3,20 +3,96 @@ use crate::cube::Cube;
 use crate::ray_intersect::Intersect;
 use crate::ray_intersect::RayIntersect;
 
-// Tipo de nodo BVH
-#[derive(Debug)]
-pub enum BVHNode {
-    Leaf { bbox_min: glm::Vec3, bbox_max: glm::Vec3, start: usize, count: usize },
-    Node { bbox_min: glm::Vec3, bbox_max: glm::Vec3, left: Box<BVHNode>, right: Box<BVHNode> },
+// Nodo del BVH aplanado (32 bytes). Los nodos se guardan en preorden: el hijo
+// izquierdo de un nodo interior está justo después de él y `offset` apunta al derecho.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FlatNode {
+    pub bbox_min: [f32; 3],
+    pub bbox_max: [f32; 3],
+    // Hoja: primer índice en `BVH::indices`. Nodo interior: posición del hijo derecho
+    pub offset: u32,
+    // Cubos de la hoja (puede quedar en 0 al quitar bloques); INTERIOR en nodos interiores
+    pub count: u32,
+}
+
+// Marca de `count` para los nodos interiores
+const INTERIOR: u32 = u32::MAX;
+
+const _: () = assert!(std::mem::size_of::<FlatNode>() == 32);
+
+impl FlatNode {
+    fn new(bmin: &glm::Vec3, bmax: &glm::Vec3) -> Self {
+        FlatNode { bbox_min: [bmin.x, bmin.y, bmin.z], bbox_max: [bmax.x, bmax.y, bmax.z], offset: 0, count: INTERIOR }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.count != INTERIOR
+    }
+
+    pub fn bounds(&self) -> (glm::Vec3, glm::Vec3) {
+        (glm::make_vec3(&self.bbox_min), glm::make_vec3(&self.bbox_max))
+    }
 }
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub struct BVH {
-    pub root: BVHNode,
+    pub nodes: Vec<FlatNode>,
     pub indices: Vec<usize>,
+    // Opciones de la construcción, para reconstruir igual cuando las ediciones lo degradan
+    pub options: BuildOptions,
+}
+
+// Profundidad máxima del árbol; acota la pila fija de los recorridos
+const MAX_TREE_DEPTH: usize = 48;
+const STACK_SIZE: usize = MAX_TREE_DEPTH + 2;
+
+// Entradas de `indices` que se toleran de más (huecos dejados por `push`) antes de reconstruir
+const REBUILD_SLACK: usize = 64;
+
+// Estrategia de partición usada al construir el BVH
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStrategy {
+    // Mediana de centroides en el eje más largo
+    Median,
+    // Surface Area Heuristic con bins
+    Sah,
+}
+
+impl SplitStrategy {
+    pub const ALL: [SplitStrategy; 2] = [SplitStrategy::Median, SplitStrategy::Sah];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SplitStrategy::Median => "median",
+            SplitStrategy::Sah => "sah",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SplitStrategy> {
+        SplitStrategy::ALL.iter().copied().find(|s| s.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BuildOptions {
+    pub strategy: SplitStrategy,
+    // Máximo de cubos por hoja (SAH puede cortar antes si le sale más barato)
+    pub max_leaf_size: usize,
 }
 
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions { strategy: SplitStrategy::Sah, max_leaf_size: 4 }
+    }
+}
+
+// Costes relativos del modelo SAH: recorrer un nodo frente a intersectar un cubo
+const SAH_TRAVERSAL_COST: f32 = 1.0;
+const SAH_INTERSECT_COST: f32 = 1.0;
+const SAH_BINS: usize = 16;
+
 // Unión de dos AABBs
 fn union_bbox(a_min: &glm::Vec3, a_max: &glm::Vec3, b_min: &glm::Vec3, b_max: &glm::Vec3) -> (glm::Vec3, glm::Vec3) {
     let min = glm::vec3(a_min.x.min(b_min.x), a_min.y.min(b_min.y), a_min.z.min(b_min.z));
@@ -24,118 +100,566 @@ fn union_bbox(a_min: &glm::Vec3, a_max: &glm::Vec3, b_min: &glm::Vec3, b_max: &g
     (min, max)
 }
 
+fn surface_area(min: &glm::Vec3, max: &glm::Vec3) -> f32 {
+    let d = *max - *min;
+    if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 { return 0.0; }
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+}
+
+fn cube_bbox(obj: &Cube) -> (glm::Vec3, glm::Vec3) {
+    (obj.center - obj.half_size, obj.center + obj.half_size)
+}
+
 // Calcular bbox para un conjunto de objetos
-fn bbox_for_indices(objects: &[Cube], indices: &[usize], start: usize, count: usize) -> (glm::Vec3, glm::Vec3) {
+fn bbox_for_indices(objects: &[Cube], indices: &[usize]) -> (glm::Vec3, glm::Vec3) {
     let mut bmin = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
     let mut bmax = glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
-    for i in start..start+count {
-        let obj = &objects[indices[i]];
-        let ex = obj.half_size.x; let ey = obj.half_size.y; let ez = obj.half_size.z;
-        let min = glm::vec3(obj.center.x - ex, obj.center.y - ey, obj.center.z - ez);
-        let max = glm::vec3(obj.center.x + ex, obj.center.y + ey, obj.center.z + ez);
-        bmin = glm::vec3(bmin.x.min(min.x), bmin.y.min(min.y), bmin.z.min(min.z));
-        bmax = glm::vec3(bmax.x.max(max.x), bmax.y.max(max.y), bmax.z.max(max.z));
+    for &i in indices {
+        let (min, max) = cube_bbox(&objects[i]);
+        (bmin, bmax) = union_bbox(&bmin, &bmax, &min, &max);
     }
     (bmin, bmax)
 }
 
+// Bbox de los centroides, que es lo que se reparte entre los hijos
+fn centroid_bounds(objects: &[Cube], indices: &[usize]) -> (glm::Vec3, glm::Vec3) {
+    let mut cmin = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut cmax = glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for &i in indices {
+        let c = objects[i].center;
+        (cmin, cmax) = union_bbox(&cmin, &cmax, &c, &c);
+    }
+    (cmin, cmax)
+}
+
 // Devolver el eje más largo de bbox
 fn longest_axis(min: &glm::Vec3, max: &glm::Vec3) -> usize {
     let diag = *max - *min;
     if diag.x > diag.y && diag.x > diag.z { 0 } else if diag.y > diag.z { 1 } else { 2 }
 }
 
-// División por mediana (placeholder, no usado)
-#[allow(dead_code)]
-fn median_split(_objects: &mut [usize], _axis: usize) {
-    // placeholder: no usado actualmente
+// División por mediana: deja la mitad de centroides más baja en `indices[..mid]`
+fn median_split(objects: &[Cube], indices: &mut [usize], axis: usize) -> usize {
+    let mid = indices.len() / 2;
+    indices.select_nth_unstable_by(mid, |&ia, &ib| {
+        objects[ia].center[axis].partial_cmp(&objects[ib].center[axis]).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    mid
+}
+
+// División SAH con bins en los tres ejes. Particiona `indices` y devuelve
+// (mid, coste estimado), o None si todos los centroides caen en el mismo bin.
+fn sah_split(objects: &[Cube], indices: &mut [usize], cmin: &glm::Vec3, cmax: &glm::Vec3, parent_area: f32) -> Option<(usize, f32)> {
+    #[derive(Clone, Copy)]
+    struct Bin { count: usize, min: glm::Vec3, max: glm::Vec3 }
+    let empty = Bin {
+        count: 0,
+        min: glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        max: glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    };
+    let bin_of = |c: f32, axis: usize| -> usize {
+        let extent = cmax[axis] - cmin[axis];
+        (((c - cmin[axis]) / extent * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+    };
+    let inv_area = if parent_area > 0.0 { 1.0 / parent_area } else { 1.0 };
+
+    // (eje, primer bin de la derecha, coste)
+    let mut best: Option<(usize, usize, f32)> = None;
+    for axis in 0..3 {
+        if cmax[axis] - cmin[axis] <= 1e-6 { continue; }
+        let mut bins = [empty; SAH_BINS];
+        for &i in indices.iter() {
+            let b = &mut bins[bin_of(objects[i].center[axis], axis)];
+            let (min, max) = cube_bbox(&objects[i]);
+            b.count += 1;
+            (b.min, b.max) = union_bbox(&b.min, &b.max, &min, &max);
+        }
+        // Barrido de derecha a izquierda: área y cuenta de los bins [k, SAH_BINS)
+        let mut right_area = [0.0f32; SAH_BINS];
+        let mut right_count = [0usize; SAH_BINS];
+        let mut acc = empty;
+        for k in (1..SAH_BINS).rev() {
+            acc.count += bins[k].count;
+            (acc.min, acc.max) = union_bbox(&acc.min, &acc.max, &bins[k].min, &bins[k].max);
+            right_area[k] = surface_area(&acc.min, &acc.max);
+            right_count[k] = acc.count;
+        }
+        let mut acc = empty;
+        for k in 1..SAH_BINS {
+            acc.count += bins[k - 1].count;
+            (acc.min, acc.max) = union_bbox(&acc.min, &acc.max, &bins[k - 1].min, &bins[k - 1].max);
+            if acc.count == 0 || right_count[k] == 0 { continue; }
+            let cost = SAH_TRAVERSAL_COST
+                + SAH_INTERSECT_COST * inv_area * (surface_area(&acc.min, &acc.max) * acc.count as f32 + right_area[k] * right_count[k] as f32);
+            if best.is_none_or(|(_, _, c)| cost < c) {
+                best = Some((axis, k, cost));
+            }
+        }
+    }
+
+    let (axis, split, cost) = best?;
+    let mut mid = 0;
+    for j in 0..indices.len() {
+        if bin_of(objects[indices[j]].center[axis], axis) < split {
+            indices.swap(mid, j);
+            mid += 1;
+        }
+    }
+    Some((mid, cost))
 }
 
-pub fn build_bvh(objects: &[Cube]) -> BVH {
+pub fn build_bvh_with(objects: &[Cube], options: BuildOptions) -> BVH {
     let mut indices: Vec<usize> = (0..objects.len()).collect();
+    let mut nodes = Vec::with_capacity(2 * objects.len().max(1));
     let n = indices.len();
-    let root = build_recursive(objects, &mut indices[..], 0, n);
-    BVH { root, indices }
+    build_recursive(objects, &mut indices[..], 0, n, 0, &options, &mut nodes);
+    BVH { nodes, indices, options }
 }
 
-fn build_recursive(objects: &[Cube], indices: &mut [usize], start: usize, count: usize) -> BVHNode {
-    let (bmin, bmax) = bbox_for_indices(objects, indices, start, count);
-    if count <= 8 {
-        return BVHNode::Leaf { bbox_min: bmin, bbox_max: bmax, start, count };
+fn build_recursive(objects: &[Cube], indices: &mut [usize], start: usize, count: usize, depth: usize, options: &BuildOptions, nodes: &mut Vec<FlatNode>) {
+    let range = &mut indices[start..start + count];
+    let (bmin, bmax) = bbox_for_indices(objects, range);
+    let node_idx = nodes.len();
+    nodes.push(FlatNode::new(&bmin, &bmax));
+    let make_leaf = |nodes: &mut Vec<FlatNode>| {
+        nodes[node_idx].offset = start as u32;
+        nodes[node_idx].count = count as u32;
+    };
+    let max_leaf = options.max_leaf_size.max(1);
+    if count <= 1 || depth >= MAX_TREE_DEPTH {
+        return make_leaf(nodes);
     }
-    let mut cmin = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
-    let mut cmax = glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
-    let _centroids: Vec<(usize, f32)> = Vec::with_capacity(count);
-    for i in start..start+count {
-        let obj = &objects[indices[i]];
-        let c = obj.center;
-        cmin = glm::vec3(cmin.x.min(c.x), cmin.y.min(c.y), cmin.z.min(c.z));
-        cmax = glm::vec3(cmax.x.max(c.x), cmax.y.max(c.y), cmax.z.max(c.z));
-    }
-    let axis = longest_axis(&cmin, &cmax);
-    indices[start..start+count].sort_by(|&ia, &ib| {
-        let ca = match axis { 0 => objects[ia].center.x, 1 => objects[ia].center.y, _ => objects[ia].center.z };
-        let cb = match axis { 0 => objects[ib].center.x, 1 => objects[ib].center.y, _ => objects[ib].center.z };
-        ca.partial_cmp(&cb).unwrap_or(std::cmp::Ordering::Equal)
-    });
-    let mid = start + count / 2;
-    let left = build_recursive(objects, indices, start, mid - start);
-    let right = build_recursive(objects, indices, mid, start + count - mid);
-    let (lmin, lmax) = match &left { BVHNode::Leaf{bbox_min, bbox_max, ..} => (*bbox_min, *bbox_max), BVHNode::Node{bbox_min, bbox_max, ..} => (*bbox_min, *bbox_max) };
-    let (rmin, rmax) = match &right { BVHNode::Leaf{bbox_min, bbox_max, ..} => (*bbox_min, *bbox_max), BVHNode::Node{bbox_min, bbox_max, ..} => (*bbox_min, *bbox_max) };
-    let (nbmin, nbmax) = union_bbox(&lmin, &lmax, &rmin, &rmax);
-    BVHNode::Node { bbox_min: nbmin, bbox_max: nbmax, left: Box::new(left), right: Box::new(right) }
-}
-
-// Intersección rayo-AABB (método slab) con inversos seguros
-pub fn ray_intersect_aabb(orig: &glm::Vec3, dir: &glm::Vec3, bmin: &glm::Vec3, bmax: &glm::Vec3) -> Option<(f32,f32)> {
-    let inv_dx = if dir.x.abs() < 1e-8 { f32::INFINITY } else { 1.0 / dir.x };
-    let inv_dy = if dir.y.abs() < 1e-8 { f32::INFINITY } else { 1.0 / dir.y };
-    let inv_dz = if dir.z.abs() < 1e-8 { f32::INFINITY } else { 1.0 / dir.z };
-
-    let mut tmin = (bmin.x - orig.x) * inv_dx;
-    let mut tmax = (bmax.x - orig.x) * inv_dx;
-    if tmin > tmax { std::mem::swap(&mut tmin, &mut tmax); }
-    let mut tymin = (bmin.y - orig.y) * inv_dy;
-    let mut tymax = (bmax.y - orig.y) * inv_dy;
-    if tymin > tymax { std::mem::swap(&mut tymin, &mut tymax); }
-    if (tmin > tymax) || (tymin > tmax) { return None; }
-    if tymin > tmin { tmin = tymin; }
-    if tymax < tmax { tmax = tymax; }
-    let mut tzmin = (bmin.z - orig.z) * inv_dz;
-    let mut tzmax = (bmax.z - orig.z) * inv_dz;
-    if tzmin > tzmax { std::mem::swap(&mut tzmin, &mut tzmax); }
-    if (tmin > tzmax) || (tzmin > tmax) { return None; }
-    if tzmin > tmin { tmin = tzmin; }
-    if tzmax < tmax { tmax = tzmax; }
-    Some((tmin, tmax))
-}
-
-// Intersectar rayo con BVH y devolver la intersección más cercana (o vacía)
-pub fn intersect_bvh(bvh: &BVH, objects: &[Cube], orig: &glm::Vec3, dir: &glm::Vec3) -> Intersect {
-    fn traverse(node: &BVHNode, bvh: &BVH, objects: &[Cube], orig: &glm::Vec3, dir: &glm::Vec3, best: &mut Intersect) {
-        match node {
-            BVHNode::Leaf { bbox_min, bbox_max, start, count } => {
-                if let Some((_t0, _t1)) = ray_intersect_aabb(orig, dir, bbox_min, bbox_max) {
-                    for i in *start..(*start + *count) {
-                        let obj_idx = bvh.indices[i];
-                        let tmp = objects[obj_idx].ray_intersect(orig, dir);
-                        if tmp.is_intersecting && tmp.distance < best.distance {
-                            *best = tmp;
-                        }
-                    }
-                }
+    let (cmin, cmax) = centroid_bounds(objects, range);
+    let split = match options.strategy {
+        SplitStrategy::Median => {
+            if count <= max_leaf { return make_leaf(nodes); }
+            median_split(objects, range, longest_axis(&cmin, &cmax))
+        }
+        SplitStrategy::Sah => {
+            let leaf_cost = SAH_INTERSECT_COST * count as f32;
+            match sah_split(objects, range, &cmin, &cmax, surface_area(&bmin, &bmax)) {
+                Some((mid, cost)) if count > max_leaf || cost < leaf_cost => mid,
+                _ if count <= max_leaf => return make_leaf(nodes),
+                // Centroides indistinguibles: partir por mitades para respetar el tamaño de hoja
+                _ => median_split(objects, range, longest_axis(&cmin, &cmax)),
             }
-            BVHNode::Node { bbox_min, bbox_max, left, right } => {
-                if let Some((_t0, _t1)) = ray_intersect_aabb(orig, dir, bbox_min, bbox_max) {
-                    traverse(left, bvh, objects, orig, dir, best);
-                    traverse(right, bvh, objects, orig, dir, best);
+        }
+    };
+    let mid = start + split;
+    build_recursive(objects, indices, start, mid - start, depth + 1, options, nodes);
+    nodes[node_idx].offset = nodes.len() as u32;
+    build_recursive(objects, indices, mid, start + count - mid, depth + 1, options, nodes);
+}
+
+// Métricas de calidad del árbol para comparar estrategias
+#[derive(Debug, Clone, Copy)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+    pub max_leaf_size: usize,
+    // Coste SAH total, normalizado por el área de la raíz
+    pub sah_cost: f32,
+}
+
+impl std::fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "nodes={} leaves={} depth={} max_leaf={} sah_cost={:.2}", self.nodes, self.leaves, self.depth, self.max_leaf_size, self.sah_cost)
+    }
+}
+
+impl BVH {
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats { nodes: self.nodes.len(), leaves: 0, depth: 0, max_leaf_size: 0, sah_cost: 0.0 };
+        let Some(root) = self.nodes.first() else { return stats; };
+        let (rmin, rmax) = root.bounds();
+        let root_area = surface_area(&rmin, &rmax);
+        let inv_root_area = if root_area > 0.0 { 1.0 / root_area } else { 0.0 };
+        let mut stack = vec![(0usize, 1usize)];
+        while let Some((idx, depth)) = stack.pop() {
+            let node = &self.nodes[idx];
+            stats.depth = stats.depth.max(depth);
+            let (bmin, bmax) = node.bounds();
+            let rel_area = surface_area(&bmin, &bmax) * inv_root_area;
+            if node.is_leaf() {
+                stats.leaves += 1;
+                stats.max_leaf_size = stats.max_leaf_size.max(node.count as usize);
+                stats.sah_cost += rel_area * SAH_INTERSECT_COST * node.count as f32;
+            } else {
+                stats.sah_cost += rel_area * SAH_TRAVERSAL_COST;
+                stack.push((idx + 1, depth + 1));
+                stack.push((node.offset as usize, depth + 1));
+            }
+        }
+        stats
+    }
+
+    // Agrega `cube` al final de `objects` y a la hoja cuya caja menos crece. El rango de la hoja
+    // se mueve al final de `indices` y el hueco que deja no se reutiliza, así que se reconstruye
+    // todo cuando la hoja pasa del doble de `max_leaf_size` o los huecos ocupan más que los cubos
+    // (ver `refit_or_rebuild`).
+    pub fn push(&mut self, objects: &mut Vec<Cube>, cube: Cube) {
+        let idx = objects.len();
+        let (cmin, cmax) = cube_bbox(&cube);
+        objects.push(cube);
+        if self.nodes.is_empty() {
+            self.nodes.push(FlatNode::new(&cmin, &cmax));
+            self.nodes[0].count = 0;
+        }
+        let growth = |node: &FlatNode| {
+            let (bmin, bmax) = node.bounds();
+            let (umin, umax) = union_bbox(&bmin, &bmax, &cmin, &cmax);
+            surface_area(&umin, &umax) - surface_area(&bmin, &bmax)
+        };
+        let mut node = 0;
+        while !self.nodes[node].is_leaf() {
+            let (left, right) = (node + 1, self.nodes[node].offset as usize);
+            node = if growth(&self.nodes[left]) <= growth(&self.nodes[right]) { left } else { right };
+        }
+        let (start, count) = (self.nodes[node].offset as usize, self.nodes[node].count as usize);
+        if start + count != self.indices.len() {
+            let entries = self.indices[start..start + count].to_vec();
+            self.nodes[node].offset = self.indices.len() as u32;
+            self.indices.extend(entries);
+        }
+        self.indices.push(idx);
+        self.nodes[node].count += 1;
+        let leaf_full = self.nodes[node].count as usize > 2 * self.options.max_leaf_size.max(1);
+        self.refit_or_rebuild(objects, leaf_full);
+    }
+
+    // Quita `objects[idx]` como `Vec::swap_remove` (el último cubo pasa a `idx`) y del árbol
+    pub fn swap_remove(&mut self, objects: &mut Vec<Cube>, idx: usize) -> Cube {
+        let last = objects.len() - 1;
+        if let Some((node, pos)) = self.find_entry(objects, idx) {
+            let end = (self.nodes[node].offset + self.nodes[node].count - 1) as usize;
+            self.indices.swap(pos, end);
+            self.nodes[node].count -= 1;
+        }
+        if idx != last && let Some((_, pos)) = self.find_entry(objects, last) {
+            self.indices[pos] = idx;
+        }
+        let removed = objects.swap_remove(idx);
+        self.refit_or_rebuild(objects, false);
+        removed
+    }
+
+    // Tras una edición: reconstruye si se pide o si `indices` tiene demasiados huecos, si no reajusta las cajas
+    fn refit_or_rebuild(&mut self, objects: &[Cube], rebuild: bool) {
+        if rebuild || self.indices.len() > 2 * objects.len() + REBUILD_SLACK {
+            *self = build_bvh_with(objects, self.options);
+        } else {
+            self.refit(objects);
+        }
+    }
+
+    // Hoja y posición en `indices` de la entrada del cubo `idx` (se busca por su centro)
+    fn find_entry(&self, objects: &[Cube], idx: usize) -> Option<(usize, usize)> {
+        let point = objects[idx].center;
+        let mut stack = vec![0usize];
+        while let Some(n) = stack.pop() {
+            let Some(node) = self.nodes.get(n) else { continue };
+            let (bmin, bmax) = node.bounds();
+            if (0..3).any(|a| point[a] < bmin[a] || point[a] > bmax[a]) { continue; }
+            if node.is_leaf() {
+                let start = node.offset as usize;
+                if let Some(k) = self.indices[start..start + node.count as usize].iter().position(|&i| i == idx) {
+                    return Some((n, start + k));
                 }
+            } else {
+                stack.push(node.offset as usize);
+                stack.push(n + 1);
             }
         }
+        None
+    }
+
+    // Recalcula las cajas de abajo hacia arriba (los hijos siempre van después del padre).
+    // Una hoja vacía queda con la caja invertida, que no suma nada al unirla.
+    pub fn refit(&mut self, objects: &[Cube]) {
+        for n in (0..self.nodes.len()).rev() {
+            let node = self.nodes[n];
+            let (bmin, bmax) = if node.is_leaf() {
+                let start = node.offset as usize;
+                bbox_for_indices(objects, &self.indices[start..start + node.count as usize])
+            } else {
+                let (lmin, lmax) = self.nodes[n + 1].bounds();
+                let (rmin, rmax) = self.nodes[node.offset as usize].bounds();
+                union_bbox(&lmin, &lmax, &rmin, &rmax)
+            };
+            self.nodes[n].bbox_min = [bmin.x, bmin.y, bmin.z];
+            self.nodes[n].bbox_max = [bmax.x, bmax.y, bmax.z];
+        }
+    }
+}
+
+// Rayo con la inversa de la dirección precalculada para los tests slab
+struct BvhRay {
+    orig: glm::Vec3,
+    inv_dir: glm::Vec3,
+}
+
+impl BvhRay {
+    fn new(orig: &glm::Vec3, dir: &glm::Vec3) -> Self {
+        let inv = |d: f32| if d.abs() < 1e-8 { f32::INFINITY.copysign(d) } else { 1.0 / d };
+        BvhRay { orig: *orig, inv_dir: glm::vec3(inv(dir.x), inv(dir.y), inv(dir.z)) }
+    }
+
+    // Distancia de entrada al AABB del nodo, o None si no lo corta dentro de [0, max_t]
+    fn hit(&self, node: &FlatNode, max_t: f32) -> Option<f32> {
+        let mut tmin = 0.0f32;
+        let mut tmax = max_t;
+        for axis in 0..3 {
+            let t1 = (node.bbox_min[axis] - self.orig[axis]) * self.inv_dir[axis];
+            let t2 = (node.bbox_max[axis] - self.orig[axis]) * self.inv_dir[axis];
+            // min/max descartan el NaN de 0 * inf cuando el origen está en el plano
+            tmin = tmin.max(t1.min(t2));
+            tmax = tmax.min(t1.max(t2));
+        }
+        if tmin <= tmax { Some(tmin) } else { None }
     }
+}
+
+// Intersectar rayo con BVH y devolver la intersección más cercana (o vacía).
+pub fn intersect_bvh(bvh: &BVH, objects: &[Cube], orig: &glm::Vec3, dir: &glm::Vec3) -> Intersect {
+    closest_hit_bvh(bvh, objects, orig, dir).1
+}
+
+// Intersección más cercana junto con el índice del cubo alcanzado (para elegir bloques).
+// Visita primero el hijo más cercano y descarta nodos que empiezan más lejos que el mejor impacto.
+pub fn closest_hit_bvh(bvh: &BVH, objects: &[Cube], orig: &glm::Vec3, dir: &glm::Vec3) -> (Option<usize>, Intersect) {
     let mut best = Intersect::empty();
     best.distance = f32::INFINITY;
-    traverse(&bvh.root, bvh, objects, orig, dir, &mut best);
-    best
-}
\ No newline at end of file
+    let mut best_idx = None;
+    let Some(root) = bvh.nodes.first() else { return (best_idx, best); };
+    let ray = BvhRay::new(orig, dir);
+    let mut stack = [(0u32, 0.0f32); STACK_SIZE];
+    let mut sp = 0;
+    if let Some(t) = ray.hit(root, best.distance) {
+        stack[sp] = (0, t);
+        sp += 1;
+    }
+    while sp > 0 {
+        sp -= 1;
+        let (idx, tnear) = stack[sp];
+        if tnear > best.distance { continue; }
+        let node = &bvh.nodes[idx as usize];
+        if node.is_leaf() {
+            let start = node.offset as usize;
+            for &obj_idx in &bvh.indices[start..start + node.count as usize] {
+                let tmp = objects[obj_idx].ray_intersect(orig, dir);
+                if tmp.is_intersecting && tmp.distance < best.distance {
+                    best = tmp;
+                    best_idx = Some(obj_idx);
+                }
+            }
+            continue;
+        }
+        let left = idx + 1;
+        let right = node.offset;
+        let hl = ray.hit(&bvh.nodes[left as usize], best.distance);
+        let hr = ray.hit(&bvh.nodes[right as usize], best.distance);
+        // El más cercano se apila el último para salir primero
+        match (hl, hr) {
+            (Some(tl), Some(tr)) => {
+                let (near, far) = if tl <= tr { ((left, tl), (right, tr)) } else { ((right, tr), (left, tl)) };
+                stack[sp] = far;
+                stack[sp + 1] = near;
+                sp += 2;
+            }
+            (Some(tl), None) => { stack[sp] = (left, tl); sp += 1; }
+            (None, Some(tr)) => { stack[sp] = (right, tr); sp += 1; }
+            (None, None) => {}
+        }
+    }
+    (best_idx, best)
+}
+
+// Consulta de oclusión (any-hit): true en cuanto algún objeto aceptado por `blocks`
+// corta el rayo antes de `max_dist`. No busca el más cercano, sale al primer impacto.
+pub fn occluded_bvh<F: Fn(&Cube) -> bool>(bvh: &BVH, objects: &[Cube], orig: &glm::Vec3, dir: &glm::Vec3, max_dist: f32, blocks: F) -> bool {
+    if bvh.nodes.is_empty() { return false; }
+    let ray = BvhRay::new(orig, dir);
+    let mut stack = [0u32; STACK_SIZE];
+    stack[0] = 0;
+    let mut sp = 1;
+    while sp > 0 {
+        sp -= 1;
+        let idx = stack[sp];
+        let node = &bvh.nodes[idx as usize];
+        if ray.hit(node, max_dist).is_none() { continue; }
+        if node.is_leaf() {
+            let start = node.offset as usize;
+            let hit = bvh.indices[start..start + node.count as usize].iter().any(|&obj_idx| {
+                let obj = &objects[obj_idx];
+                if !blocks(obj) { return false; }
+                let tmp = obj.ray_intersect(orig, dir);
+                tmp.is_intersecting && tmp.distance <= max_dist
+            });
+            if hit { return true; }
+        } else {
+            stack[sp] = node.offset;
+            stack[sp + 1] = idx + 1;
+            sp += 2;
+        }
+    }
+    false
+}
+
+// Índice de un cubo que contiene `point` y cumple `accept` (consulta de ocupación)
+pub fn find_containing_bvh<F: Fn(&Cube) -> bool>(bvh: &BVH, objects: &[Cube], point: &glm::Vec3, accept: F) -> Option<usize> {
+    if bvh.nodes.is_empty() { return None; }
+    let mut stack = [0u32; STACK_SIZE];
+    stack[0] = 0;
+    let mut sp = 1;
+    while sp > 0 {
+        sp -= 1;
+        let idx = stack[sp];
+        let node = &bvh.nodes[idx as usize];
+        let (bmin, bmax) = node.bounds();
+        if (0..3).any(|a| point[a] < bmin[a] || point[a] > bmax[a]) { continue; }
+        if node.is_leaf() {
+            let start = node.offset as usize;
+            let found = bvh.indices[start..start + node.count as usize].iter()
+                .find(|&&obj_idx| accept(&objects[obj_idx]) && objects[obj_idx].contains(point));
+            if let Some(&obj_idx) = found { return Some(obj_idx); }
+        } else {
+            stack[sp] = node.offset;
+            stack[sp + 1] = idx + 1;
+            sp += 2;
+        }
+    }
+    None
+}
+
+// Índices de los cubos aceptados por `accept` cuya caja toca la caja [min, max]
+pub fn overlapping_bvh<F: Fn(&Cube) -> bool>(bvh: &BVH, objects: &[Cube], min: &glm::Vec3, max: &glm::Vec3, accept: F) -> Vec<usize> {
+    let mut found = Vec::new();
+    if bvh.nodes.is_empty() { return found; }
+    let overlaps = |bmin: &glm::Vec3, bmax: &glm::Vec3| (0..3).all(|a| bmin[a] <= max[a] && bmax[a] >= min[a]);
+    let mut stack = [0u32; STACK_SIZE];
+    stack[0] = 0;
+    let mut sp = 1;
+    while sp > 0 {
+        sp -= 1;
+        let idx = stack[sp];
+        let node = &bvh.nodes[idx as usize];
+        let (bmin, bmax) = node.bounds();
+        if !overlaps(&bmin, &bmax) { continue; }
+        if node.is_leaf() {
+            let start = node.offset as usize;
+            for &obj_idx in &bvh.indices[start..start + node.count as usize] {
+                let (omin, omax) = cube_bbox(&objects[obj_idx]);
+                if accept(&objects[obj_idx]) && overlaps(&omin, &omax) {
+                    found.push(obj_idx);
+                }
+            }
+        } else {
+            stack[sp] = node.offset;
+            stack[sp + 1] = idx + 1;
+            sp += 2;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::materials::MaterialId;
+    use crate::ray_intersect::{Material, RayIntersect};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn cube(center: glm::Vec3, half_size: glm::Vec3) -> Cube {
+        let material = Material::new_color(MaterialId::Custom, Color::new(255, 255, 255, 255));
+        Cube { center, half_size, rot_y: 0.0, material, top_material: None, radius: glm::length(&half_size) }
+    }
+
+    // Cubos de tamaños variados repartidos en una caja de 20 x 8 x 20
+    fn random_cubes(rng: &mut StdRng, n: usize) -> Vec<Cube> {
+        (0..n).map(|_| {
+            let center = glm::vec3(rng.random_range(0.0..20.0), rng.random_range(0.0..8.0), rng.random_range(0.0..20.0));
+            let half = glm::vec3(rng.random_range(0.1..0.8), rng.random_range(0.1..0.8), rng.random_range(0.1..0.8));
+            cube(center, half)
+        }).collect()
+    }
+
+    fn random_ray(rng: &mut StdRng) -> (glm::Vec3, glm::Vec3) {
+        let orig = glm::vec3(rng.random_range(-5.0..25.0), rng.random_range(-2.0..10.0), rng.random_range(-5.0..25.0));
+        let dir = glm::vec3(rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0));
+        (orig, glm::normalize(&dir))
+    }
+
+    // Impacto más cercano probando todos los cubos
+    fn brute_force(objects: &[Cube], orig: &glm::Vec3, dir: &glm::Vec3) -> Option<f32> {
+        objects.iter()
+            .map(|c| c.ray_intersect(orig, dir))
+            .filter(|h| h.is_intersecting)
+            .map(|h| h.distance)
+            .min_by(f32::total_cmp)
+    }
+
+    // Compara `closest_hit_bvh` con la fuerza bruta en `rays` rayos al azar
+    fn assert_matches_brute_force(bvh: &BVH, objects: &[Cube], rng: &mut StdRng, rays: usize) {
+        for _ in 0..rays {
+            let (orig, dir) = random_ray(rng);
+            let (idx, hit) = closest_hit_bvh(bvh, objects, &orig, &dir);
+            let expected = brute_force(objects, &orig, &dir);
+            assert_eq!(idx.is_some(), expected.is_some(), "ray {:?} {:?}", orig, dir);
+            if let Some(d) = expected {
+                assert!((hit.distance - d).abs() < 1e-4, "distance {} vs {}", hit.distance, d);
+            }
+        }
+    }
+
+    #[test]
+    fn closest_hit_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let objects = random_cubes(&mut rng, 300);
+        for strategy in SplitStrategy::ALL {
+            for max_leaf_size in [1, 4] {
+                let bvh = build_bvh_with(&objects, BuildOptions { strategy, max_leaf_size });
+                assert_matches_brute_force(&bvh, &objects, &mut rng, 2000);
+            }
+        }
+    }
+
+    // Ediciones al azar como las del editor: el árbol sigue dando los mismos impactos que la
+    // fuerza bruta y ni `indices` ni las hojas crecen sin límite
+    #[test]
+    fn closest_hit_matches_brute_force_after_edits() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for strategy in SplitStrategy::ALL {
+            let mut objects = random_cubes(&mut rng, 200);
+            let options = BuildOptions { strategy, max_leaf_size: 4 };
+            let mut bvh = build_bvh_with(&objects, options);
+            for step in 0..3000 {
+                if objects.is_empty() || rng.random_range(0..10) < 6 {
+                    let c = random_cubes(&mut rng, 1).pop().unwrap();
+                    bvh.push(&mut objects, c);
+                } else {
+                    let idx = rng.random_range(0..objects.len());
+                    bvh.swap_remove(&mut objects, idx);
+                }
+                assert!(bvh.indices.len() <= 2 * objects.len() + REBUILD_SLACK, "indices {} for {} cubes", bvh.indices.len(), objects.len());
+                assert!(bvh.stats().max_leaf_size <= 2 * options.max_leaf_size);
+                if step % 100 == 0 {
+                    assert_matches_brute_force(&bvh, &objects, &mut rng, 200);
+                }
+            }
+            assert_matches_brute_force(&bvh, &objects, &mut rng, 2000);
+        }
+    }
+
+    // Calidad del árbol en el diorama de referencia: con el coste SAH de `stats` (menor es
+    // mejor) los bins SAH deben ganarle a la mediana; hoy da ~36 contra ~49
+    #[test]
+    fn sah_beats_median_on_the_diorama() {
+        let scene = crate::scene::Scene::load("escenas/diorama.toml").unwrap();
+        let cost = |strategy| build_bvh_with(&scene.objects, BuildOptions { strategy, ..BuildOptions::default() }).stats();
+        let (median, sah) = (cost(SplitStrategy::Median), cost(SplitStrategy::Sah));
+        assert!(sah.sah_cost < median.sah_cost * 0.85, "sah {} vs median {}", sah, median);
+        assert!(sah.depth <= MAX_TREE_DEPTH && median.depth <= MAX_TREE_DEPTH);
+    }
+}
@@ -0,0 +1,150 @@
+use nalgebra_glm as glm;
+
+// Tipo de proyección de la cámara
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
+
+impl Projection {
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Projection> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" | "ortho" => Some(Projection::Orthographic),
+            _ => None,
+        }
+    }
+}
+
+// Pitch de la vista isométrica verdadera (-atan(1/sqrt(2))): los tres ejes con el mismo escorzo
+pub const ISO_PITCH: f32 = -0.615_479_7;
+
+// Cámara: pose (posición, yaw, pitch), campo de visión, aspecto y proyección.
+// La base (forward/right/up) se recalcula solo al cambiar la orientación.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: glm::Vec3,
+    yaw: f32,
+    pitch: f32,
+    // campo de visión vertical en grados (perspectiva)
+    pub fov_deg: f32,
+    // ancho / alto de la imagen
+    pub aspect: f32,
+    pub projection: Projection,
+    // alto visible en unidades de mundo (ortográfica)
+    pub ortho_height: f32,
+    forward: glm::Vec3,
+    right: glm::Vec3,
+    up: glm::Vec3,
+}
+
+impl Camera {
+    pub const DEFAULT_FOV_DEG: f32 = 60.0;
+    pub const DEFAULT_ORTHO_HEIGHT: f32 = 20.0;
+
+    pub fn new(position: glm::Vec3, yaw: f32, pitch: f32) -> Self {
+        let mut camera = Camera {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov_deg: Self::DEFAULT_FOV_DEG,
+            aspect: 1.0,
+            projection: Projection::Perspective,
+            ortho_height: Self::DEFAULT_ORTHO_HEIGHT,
+            forward: glm::vec3(0.0, 0.0, -1.0),
+            right: glm::vec3(1.0, 0.0, 0.0),
+            up: glm::vec3(0.0, 1.0, 0.0),
+        };
+        camera.set_orientation(yaw, pitch);
+        camera
+    }
+
+    pub fn yaw(&self) -> f32 { self.yaw }
+    pub fn pitch(&self) -> f32 { self.pitch }
+    pub fn forward(&self) -> glm::Vec3 { self.forward }
+    pub fn right(&self) -> glm::Vec3 { self.right }
+
+    // Fija yaw/pitch (radianes); el pitch se limita para no pasar por la vertical
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        let max_pitch = std::f32::consts::FRAC_PI_2 - 0.01_f32;
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-max_pitch, max_pitch);
+
+        let cp = self.pitch.cos();
+        let sp = self.pitch.sin();
+        let cy = self.yaw.cos();
+        let sy = self.yaw.sin();
+        self.forward = glm::vec3(-sy * cp, sp, -cy * cp);
+        let world_up = glm::vec3(0.0, 1.0, 0.0);
+        let mut right = glm::cross(&self.forward, &world_up);
+        if glm::length(&right) < 1e-6 { right = glm::vec3(1.0, 0.0, 0.0); }
+        self.right = glm::normalize(&right);
+        let mut up = glm::cross(&self.right, &self.forward);
+        if glm::length(&up) < 1e-6 { up = glm::vec3(0.0, 1.0, 0.0); }
+        self.up = glm::normalize(&up);
+    }
+
+    pub fn rotate(&mut self, d_yaw: f32, d_pitch: f32) {
+        self.set_orientation(self.yaw + d_yaw, self.pitch + d_pitch);
+    }
+
+    // Vista isométrica ortográfica que encuadra la caja [min, max].
+    // `corner` (0..3) elige desde qué esquina del diorama se mira.
+    pub fn isometric(min: &glm::Vec3, max: &glm::Vec3, corner: i32, aspect: f32) -> Camera {
+        use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+        let yaw = FRAC_PI_4 + corner.rem_euclid(4) as f32 * FRAC_PI_2;
+        let center = (*min + *max) * 0.5;
+        let radius = glm::length(&(*max - *min)) * 0.5;
+        let mut camera = Camera::new(center, yaw, ISO_PITCH);
+        camera.projection = Projection::Orthographic;
+        camera.aspect = aspect;
+        camera.position = center - camera.forward * (radius + 1.0);
+        // Alto que abarca las 8 esquinas de la caja proyectadas en el plano de imagen
+        let mut half_w = 0.0f32;
+        let mut half_h = 0.0f32;
+        for i in 0..8 {
+            let p = glm::vec3(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            let d = p - center;
+            half_w = half_w.max(glm::dot(&d, &camera.right).abs());
+            half_h = half_h.max(glm::dot(&d, &camera.up).abs());
+        }
+        camera.ortho_height = 2.0 * half_h.max(half_w / aspect.max(1e-3)) * 1.05;
+        camera
+    }
+
+    // Zoom ortográfico: `factor` > 1 acerca (reduce el volumen visible)
+    pub fn zoom(&mut self, factor: f32) {
+        self.ortho_height = (self.ortho_height / factor).max(0.01);
+    }
+
+    // Rayo (origen, dirección) por el punto (u, v) de la imagen, con u, v en [0, 1]
+    // desde la esquina superior izquierda
+    pub fn generate_ray(&self, u: f32, v: f32) -> (glm::Vec3, glm::Vec3) {
+        let sx = (2.0 * u - 1.0) * self.aspect;
+        let sy = 1.0 - 2.0 * v;
+        match self.projection {
+            Projection::Perspective => {
+                let scale = (self.fov_deg.to_radians() * 0.5).tan();
+                let dir = self.right * (sx * scale) + self.up * (sy * scale) + self.forward;
+                (self.position, glm::normalize(&dir))
+            }
+            Projection::Orthographic => {
+                let half = self.ortho_height * 0.5;
+                let origin = self.position + self.right * (sx * half) + self.up * (sy * half);
+                (origin, self.forward)
+            }
+        }
+    }
+}
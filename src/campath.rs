@@ -0,0 +1,154 @@
+use std::f32::consts::PI;
+use nalgebra_glm as glm;
+
+use crate::camera::Camera;
+use crate::scene::parse_document;
+
+// Recorridos de cámara: keyframes (posición, yaw, pitch) que se reproducen con
+// interpolación Catmull-Rom. Se guardan con el mismo formato de texto que las escenas:
+//
+//   [[keyframe]]
+//   position = [x, y, z]
+//   yaw = 0.5
+//   pitch = -0.1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub position: glm::Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Keyframe {
+    pub fn from_camera(camera: &Camera) -> Keyframe {
+        Keyframe { position: camera.position, yaw: camera.yaw(), pitch: camera.pitch() }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.set_orientation(self.yaw, self.pitch);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    pub keys: Vec<Keyframe>,
+}
+
+// Catmull-Rom uniforme entre p1 y p2 (t en [0, 1])
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T> + std::ops::Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+// Diferencia de ángulos llevada a (-pi, pi]
+fn wrap_angle(a: f32) -> f32 {
+    let a = (a + PI).rem_euclid(2.0 * PI) - PI;
+    if a <= -PI { a + 2.0 * PI } else { a }
+}
+
+impl CameraPath {
+    // Segundos que tarda cada tramo entre dos keyframes a velocidad 1
+    pub const DEFAULT_SEGMENT_SECONDS: f32 = 2.0;
+
+    pub fn load(path: &str) -> Result<CameraPath, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("failed to open {:?}: {}", path, e))?;
+        CameraPath::parse(&text).map_err(|e| format!("{}:{}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<CameraPath, String> {
+        let doc = parse_document(text)?;
+        let mut keys = Vec::new();
+        for t in doc.arrays.get("keyframe").map(|v| v.as_slice()).unwrap_or(&[]) {
+            keys.push(Keyframe { position: t.req_vec3("position")?, yaw: t.req_f32("yaw")?, pitch: t.req_f32("pitch")? });
+        }
+        Ok(CameraPath { keys })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut text = String::from("# Recorrido de cámara: keyframes en orden de reproducción\n");
+        for k in &self.keys {
+            text += &format!(
+                "\n[[keyframe]]\nposition = [{}, {}, {}]\nyaw = {}\npitch = {}\n",
+                k.position.x, k.position.y, k.position.z, k.yaw, k.pitch
+            );
+        }
+        std::fs::write(path, text).map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+
+    // Duración total en segundos con `segment_seconds` por tramo
+    pub fn duration(&self, segment_seconds: f32) -> f32 {
+        self.keys.len().saturating_sub(1) as f32 * segment_seconds
+    }
+
+    // Pose en el instante `time` (se limita al recorrido). Las posiciones siguen una
+    // Catmull-Rom y los ángulos también, tomando siempre el giro más corto entre keyframes.
+    pub fn sample(&self, time: f32, segment_seconds: f32) -> Option<Keyframe> {
+        let n = self.keys.len();
+        if n < 2 { return self.keys.first().copied(); }
+        let s = (time / segment_seconds.max(1e-3)).clamp(0.0, (n - 1) as f32);
+        let seg = (s.floor() as usize).min(n - 2);
+        let t = s - seg as f32;
+        // índices de los 4 puntos de control, repitiendo los extremos
+        let idx = [seg.saturating_sub(1), seg, seg + 1, (seg + 2).min(n - 1)];
+        let p = idx.map(|i| self.keys[i].position);
+        // yaw desenrollado respecto al keyframe anterior para no dar la vuelta larga
+        let mut yaw = [0.0f32; 4];
+        yaw[0] = self.keys[idx[0]].yaw;
+        for i in 1..4 {
+            yaw[i] = yaw[i - 1] + wrap_angle(self.keys[idx[i]].yaw - self.keys[idx[i - 1]].yaw);
+        }
+        let pitch = idx.map(|i| self.keys[i].pitch);
+        Some(Keyframe {
+            position: catmull_rom(p[0], p[1], p[2], p[3], t),
+            yaw: catmull_rom(yaw[0], yaw[1], yaw[2], yaw[3], t),
+            pitch: catmull_rom(pitch[0], pitch[1], pitch[2], pitch[3], t),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: f32, y: f32, z: f32, yaw: f32, pitch: f32) -> Keyframe {
+        Keyframe { position: glm::vec3(x, y, z), yaw, pitch }
+    }
+
+    #[test]
+    fn sample_passes_through_keyframes() {
+        let path = CameraPath {
+            keys: vec![
+                key(0.0, 2.0, 0.0, 0.0, 0.0),
+                key(4.0, 3.0, 1.0, 3.0, -0.2),
+                // cruza ±pi: el giro corto desde 3.0 es de ~0.38 rad
+                key(6.0, 2.5, 5.0, -2.9, 0.1),
+                key(2.0, 4.0, 8.0, -2.0, -0.3),
+            ],
+        };
+        let seg = CameraPath::DEFAULT_SEGMENT_SECONDS;
+        assert_eq!(path.duration(seg), 3.0 * seg);
+        for (i, k) in path.keys.iter().enumerate() {
+            let s = path.sample(i as f32 * seg, seg).unwrap();
+            assert!(glm::length(&(s.position - k.position)) < 1e-4, "key {}: {:?}", i, s);
+            assert!(wrap_angle(s.yaw - k.yaw).abs() < 1e-4, "key {}: yaw {} vs {}", i, s.yaw, k.yaw);
+            assert!((s.pitch - k.pitch).abs() < 1e-4, "key {}: {:?}", i, s);
+        }
+        // fuera del recorrido se queda en los extremos
+        assert_eq!(path.sample(-1.0, seg), path.sample(0.0, seg));
+        assert_eq!(path.sample(100.0, seg), path.sample(path.duration(seg), seg));
+        // a mitad del tramo que cruza ±pi el yaw va por el lado corto
+        let mid = path.sample(1.5 * seg, seg).unwrap();
+        assert!(wrap_angle(mid.yaw - PI).abs() < 0.3, "yaw {}", mid.yaw);
+    }
+
+    #[test]
+    fn sample_with_few_keyframes() {
+        assert_eq!(CameraPath::default().sample(1.0, 2.0), None);
+        let one = CameraPath { keys: vec![key(1.0, 2.0, 3.0, 0.5, 0.0)] };
+        assert_eq!(one.sample(5.0, 2.0), Some(one.keys[0]));
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::render::{Accumulator, RenderContext};
+
+// Capturas desde el visor: nombres con fecha y hora y re-render en alta resolución
+
+// Fecha y hora UTC actuales como "AAAAMMDD_HHMMSS"
+pub fn timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    // días desde 1970-01-01 a fecha civil (algoritmo de Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}_{:02}{:02}{:02}", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// Ruta `dir/prefijo_<fecha>.png` que no pisa un archivo existente (agrega _2, _3...)
+pub fn timestamped_path(dir: &str, prefix: &str) -> PathBuf {
+    let stamp = timestamp();
+    let mut path = Path::new(dir).join(format!("{}_{}.png", prefix, stamp));
+    let mut n = 2;
+    while path.exists() {
+        path = Path::new(dir).join(format!("{}_{}_{}.png", prefix, stamp, n));
+        n += 1;
+    }
+    path
+}
+
+// Guarda el framebuffer en `path`, creando la carpeta si hace falta
+pub fn save(fb: &Framebuffer, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| format!("failed to create {:?}: {}", parent, e))?;
+    }
+    fb.save_png(&path.to_string_lossy())
+}
+
+// Vuelve a renderizar la vista a `width`x`height` con `samples` muestras por pixel
+pub fn render_still(ctx: &RenderContext, camera: &Camera, width: u32, height: u32, samples: u32) -> Framebuffer {
+    let mut fb = Framebuffer::new(width, height, Color::BLACK);
+    let mut camera = *camera;
+    camera.aspect = width as f32 / height as f32;
+    let mut accum = Accumulator::new(width, height);
+    for _ in 0..samples.max(1) {
+        accum.accumulate(ctx, &camera);
+    }
+    accum.resolve(&mut fb);
+    fb
+}
+
+// Grabación de una secuencia de PNG numerados (frame_00000.png, ...) en una carpeta propia
+pub struct Recorder {
+    dir: PathBuf,
+    frames: u32,
+}
+
+impl Recorder {
+    // Crea `base/grabacion_<fecha>` (o `base` tal cual si `fresh_dir` es false)
+    pub fn start(base: &str, fresh_dir: bool) -> Result<Recorder, String> {
+        let dir = if fresh_dir {
+            Path::new(base).join(format!("grabacion_{}", timestamp()))
+        } else {
+            PathBuf::from(base)
+        };
+        std::fs::create_dir_all(&dir).map_err(|e| format!("failed to create {:?}: {}", dir, e))?;
+        Ok(Recorder { dir, frames: 0 })
+    }
+
+    pub fn dir(&self) -> &Path { &self.dir }
+    pub fn frames(&self) -> u32 { self.frames }
+
+    pub fn save_frame(&mut self, fb: &Framebuffer) -> Result<(), String> {
+        let path = self.dir.join(format!("frame_{:05}.png", self.frames));
+        fb.save_png(&path.to_string_lossy())?;
+        self.frames += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+// Lectura mínima de argumentos de línea de comandos.
+// Acepta tanto `--nombre valor` como `--nombre=valor`.
+
+pub fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+pub fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    for (i, a) in args.iter().enumerate() {
+        if let Some(rest) = a.strip_prefix(name) {
+            if let Some(v) = rest.strip_prefix('=') {
+                return Some(v);
+            }
+            if rest.is_empty() {
+                return args.get(i + 1).map(|s| s.as_str());
+            }
+        }
+    }
+    None
+}
+
+pub fn arg_parse<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    arg_value(args, name).and_then(|v| v.parse::<T>().ok())
+}
+
+// Lista de números separados por comas, p. ej. `--cam-pos 1,2.5,-3`
+pub fn arg_floats(args: &[String], name: &str) -> Option<Vec<f32>> {
+    arg_value(args, name).and_then(|v| v.split(',').map(|x| x.trim().parse::<f32>().ok()).collect())
+}
@@ -0,0 +1,25 @@
+use nalgebra_glm as glm;
+
+// Color RGBA de 8 bits propio del trazador, independiente de raylib
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0, 0, 0, 255);
+    pub const WHITE: Color = Color::new(255, 255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    // Valor lineal aproximado (gamma 2.2) para sumar luz
+    pub fn to_linear(self) -> glm::Vec3 {
+        let f = |c: u8| (c as f32 / 255.0).powf(2.2);
+        glm::vec3(f(self.r), f(self.g), f(self.b))
+    }
+}
@@ -0,0 +1,190 @@
+use raylib::prelude::*;
+use nalgebra_glm as glm;
+
+use crate::camera::{Camera, Projection};
+use crate::walk::{Collider, WALK_SPEED, Walker};
+
+// Control de la cámara: teclado, mouse capturado y rueda. Toda la entrada de movimiento pasa
+// por aquí; main solo llama a `update` una vez por frame. La cámara vuela libremente o, en
+// modo caminar (F), se mueve como un jugador con gravedad y choques (ver walk.rs).
+
+// Entrada de un frame, ya leída del teclado y el mouse. Los ejes van de -1 a 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CameraInput {
+    // derecha / izquierda (flechas)
+    pub strafe: f32,
+    // adelante / atrás en la dirección de vista (flechas)
+    pub forward: f32,
+    // subir / bajar en y del mundo (W / S)
+    pub lift: f32,
+    // acercar / alejar en la dirección de vista, más lento (Z / X, PageUp / PageDown)
+    pub dolly: f32,
+    // giro con teclado: yaw (A / D) y pitch (Q / E)
+    pub turn: f32,
+    pub tilt: f32,
+    // zoom ortográfico con + / -
+    pub ortho_zoom: f32,
+    // movimiento del mouse en pixeles (solo con el mouse capturado) y pasos de la rueda
+    pub mouse_dx: f32,
+    pub mouse_dy: f32,
+    pub wheel: f32,
+    pub sprint: bool,
+    // saltar (Espacio, solo al caminar) y alternar entre volar y caminar (F)
+    pub jump: bool,
+    pub toggle_walk: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraController {
+    // unidades por segundo
+    pub move_speed: f32,
+    // rad/s para los giros con teclado
+    pub rot_speed: f32,
+    // radianes por pixel de mouse
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    // multiplicador de velocidad con Shift
+    pub sprint_factor: f32,
+    // el botón derecho captura el mouse (se apaga mientras el editor usa los clics)
+    pub right_button_capture: bool,
+    // con Ctrl apretado el teclado no mueve la cámara (el editor usa Ctrl+Z / Ctrl+Y / Ctrl+S)
+    pub ctrl_shortcuts: bool,
+    mouse_captured: bool,
+    // Some mientras se camina
+    walker: Option<Walker>,
+}
+
+// Velocidad del dolly (Z / X) respecto a la de movimiento
+const DOLLY_FACTOR: f32 = 0.5;
+// Cada paso de la rueda multiplica el campo de visión por este factor
+const WHEEL_FOV_STEP: f32 = 0.9;
+const MIN_FOV_DEG: f32 = 10.0;
+const MAX_FOV_DEG: f32 = 120.0;
+// Al caminar Shift acelera menos que al volar
+const WALK_SPRINT_FACTOR: f32 = 1.5;
+
+fn axis(rl: &RaylibHandle, positive: &[KeyboardKey], negative: &[KeyboardKey]) -> f32 {
+    let down = |keys: &[KeyboardKey]| keys.iter().any(|k| rl.is_key_down(*k)) as i32 as f32;
+    down(positive) - down(negative)
+}
+
+impl CameraController {
+    pub const DEFAULT_MOUSE_SENSITIVITY: f32 = 0.003;
+    pub const DEFAULT_SPRINT_FACTOR: f32 = 3.0;
+
+    pub fn new(move_speed: f32, rot_speed: f32) -> Self {
+        CameraController {
+            move_speed,
+            rot_speed,
+            mouse_sensitivity: Self::DEFAULT_MOUSE_SENSITIVITY,
+            invert_y: false,
+            sprint_factor: Self::DEFAULT_SPRINT_FACTOR,
+            right_button_capture: true,
+            ctrl_shortcuts: false,
+            mouse_captured: false,
+            walker: None,
+        }
+    }
+
+    pub fn walking(&self) -> bool { self.walker.is_some() }
+    pub fn mouse_captured(&self) -> bool { self.mouse_captured }
+
+    // Captura o suelta el mouse (cursor oculto y movimiento relativo)
+    pub fn set_mouse_captured(&mut self, rl: &mut RaylibHandle, captured: bool) {
+        if captured { rl.disable_cursor() } else { rl.enable_cursor() }
+        self.mouse_captured = captured;
+    }
+
+    // Lee la entrada del frame. El botón derecho (o Tab) captura / suelta el mouse.
+    pub fn read_input(&mut self, rl: &mut RaylibHandle) -> CameraInput {
+        use KeyboardKey::*;
+        let right_click = self.right_button_capture && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT);
+        if right_click || rl.is_key_pressed(KEY_TAB) {
+            let captured = !self.mouse_captured;
+            self.set_mouse_captured(rl, captured);
+        }
+        let (mouse_dx, mouse_dy) = if self.mouse_captured {
+            let d = rl.get_mouse_delta();
+            (d.x, d.y)
+        } else {
+            (0.0, 0.0)
+        };
+        let input = CameraInput {
+            strafe: axis(rl, &[KEY_RIGHT], &[KEY_LEFT]),
+            forward: axis(rl, &[KEY_UP], &[KEY_DOWN]),
+            lift: axis(rl, &[KEY_W], &[KEY_S]),
+            dolly: axis(rl, &[KEY_Z, KEY_PAGE_UP], &[KEY_X, KEY_PAGE_DOWN]),
+            turn: axis(rl, &[KEY_D], &[KEY_A]),
+            tilt: axis(rl, &[KEY_E], &[KEY_Q]),
+            ortho_zoom: axis(rl, &[KEY_EQUAL], &[KEY_MINUS]),
+            mouse_dx,
+            mouse_dy,
+            wheel: rl.get_mouse_wheel_move(),
+            sprint: rl.is_key_down(KEY_LEFT_SHIFT) || rl.is_key_down(KEY_RIGHT_SHIFT),
+            jump: rl.is_key_down(KEY_SPACE),
+            toggle_walk: rl.is_key_pressed(KEY_F),
+        };
+        let ctrl = rl.is_key_down(KEY_LEFT_CONTROL) || rl.is_key_down(KEY_RIGHT_CONTROL);
+        if self.ctrl_shortcuts && ctrl {
+            // solo queda el mouse
+            return CameraInput { mouse_dx, mouse_dy, wheel: input.wheel, ..CameraInput::default() };
+        }
+        input
+    }
+
+    // Aplica la entrada a la cámara durante `dt` segundos; al caminar choca contra `world`
+    pub fn apply(&mut self, camera: &mut Camera, input: &CameraInput, world: &Collider, dt: f32) {
+        if input.toggle_walk {
+            self.walker = match self.walker {
+                Some(_) => None,
+                None => Some(Walker::default()),
+            };
+            println!("camera: {}", if self.walking() { "walk mode" } else { "fly mode" });
+        }
+
+        // Orientación: teclado a velocidad fija y mouse por pixel (mover hacia arriba mira arriba)
+        let invert = if self.invert_y { -1.0 } else { 1.0 };
+        let d_yaw = input.turn * self.rot_speed * dt + input.mouse_dx * self.mouse_sensitivity;
+        let d_pitch = input.tilt * self.rot_speed * dt - input.mouse_dy * self.mouse_sensitivity * invert;
+        if d_yaw != 0.0 || d_pitch != 0.0 {
+            camera.rotate(d_yaw, d_pitch);
+        }
+
+        let forward = camera.forward();
+        let right = camera.right();
+        if let Some(walker) = self.walker.as_mut() {
+            // Caminando: las flechas mueven en el plano horizontal; W / S y el dolly no cuentan
+            let flat = glm::vec3(forward.x, 0.0, forward.z);
+            let flat = if glm::length(&flat) > 1e-6 { glm::normalize(&flat) } else { flat };
+            let mut wish = flat * input.forward + right * input.strafe;
+            if glm::length(&wish) > 1.0 { wish = glm::normalize(&wish); }
+            let speed = WALK_SPEED * if input.sprint { WALK_SPRINT_FACTOR } else { 1.0 };
+            walker.step(world, camera, &(wish * speed), input.jump, dt);
+        } else {
+            let speed = self.move_speed * if input.sprint { self.sprint_factor } else { 1.0 };
+            camera.position += right * (input.strafe * speed * dt);
+            camera.position += forward * ((input.forward + input.dolly * DOLLY_FACTOR) * speed * dt);
+            camera.position.y += input.lift * speed * dt;
+        }
+
+        // Zoom: la rueda cambia el campo de visión (o el alto visible en ortográfica)
+        match camera.projection {
+            Projection::Perspective => {
+                if input.wheel != 0.0 {
+                    camera.fov_deg = (camera.fov_deg * WHEEL_FOV_STEP.powf(input.wheel)).clamp(MIN_FOV_DEG, MAX_FOV_DEG);
+                }
+            }
+            Projection::Orthographic => {
+                let steps = input.ortho_zoom * 1.5 * dt;
+                if steps != 0.0 || input.wheel != 0.0 {
+                    camera.zoom((1.0 + steps.abs()).powf(steps.signum()) / WHEEL_FOV_STEP.powf(input.wheel));
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, rl: &mut RaylibHandle, camera: &mut Camera, world: &Collider, dt: f32) {
+        let input = self.read_input(rl);
+        self.apply(camera, &input, world, dt);
+    }
+}
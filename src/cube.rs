@@ -24,6 +24,12 @@ impl Cube {
         let sa = angle.sin();
         glm::vec3(v.x, ca * v.y - sa * v.z, sa * v.y + ca * v.z)
     }
+
+    // ¿El punto está dentro del cubo (bordes incluidos)?
+    pub fn contains(&self, point: &glm::Vec3) -> bool {
+        let local = Cube::rotate_y(&(point - self.center), -self.rot_y);
+        local.x.abs() <= self.half_size.x && local.y.abs() <= self.half_size.y && local.z.abs() <= self.half_size.z
+    }
 }
 
 impl RayIntersect for Cube {
@@ -136,10 +142,10 @@ impl RayIntersect for Cube {
         };
 
         let mut mat = self.material.clone();
-        if normal_local.y.abs() > 0.5 && normal_local.y > 0.0 {
-            if let Some(ref top) = self.top_material {
-                mat = top.clone();
-            }
+        if normal_local.y.abs() > 0.5 && normal_local.y > 0.0
+            && let Some(ref top) = self.top_material
+        {
+            mat = top.clone();
         }
 
         let face = if normal_local.x.abs() > 0.5 {
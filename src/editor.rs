@@ -0,0 +1,252 @@
+use nalgebra_glm as glm;
+
+use crate::bvh::{BVH, closest_hit_bvh, overlapping_bvh};
+use crate::camera::Camera;
+use crate::cube::Cube;
+use crate::materials::MaterialId;
+use crate::ray_intersect::Material;
+use crate::render::prepare_cube;
+use crate::scene::Scene;
+
+// Editor de bloques dentro de la ventana: se apunta con el mismo rayo que la inspección,
+// se quita el bloque apuntado, se coloca uno nuevo contra la cara apuntada o se le cambia el
+// material por el elegido en la paleta (las fábricas de materials.rs). La escena, su copia
+// de render y el BVH se actualizan en el momento, sin reconstruir nada.
+
+// Margen (en celdas) para no contar como choque a los vecinos: con el diorama girado las
+// cajas de bloques vecinos se solapan un poco
+const OVERLAP_MARGIN: f32 = 0.1;
+
+// Estado del editor entre frames: material elegido de la paleta
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Editor {
+    selected: usize,
+}
+
+// Bloque apuntado: índice en la copia de render y normal de la cara alcanzada
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+    pub index: usize,
+    pub normal: glm::Vec3,
+}
+
+impl Editor {
+    pub fn material(&self) -> MaterialId {
+        MaterialId::ALL[self.selected]
+    }
+
+    // Siguiente (`step` = 1) o anterior (-1) material de la paleta
+    pub fn cycle(&mut self, step: i32) {
+        let n = MaterialId::ALL.len() as i32;
+        self.selected = (self.selected as i32 + step).rem_euclid(n) as usize;
+    }
+
+    // Línea de estado para el panel en pantalla
+    pub fn status(&self) -> String {
+        format!("editor: {} ({}/{})", self.material().name(), self.selected + 1, MaterialId::ALL.len())
+    }
+}
+
+// Bloque bajo el punto (u, v) de la imagen, como en inspect::pick
+pub fn target(objects: &[Cube], bvh: Option<&BVH>, camera: &Camera, u: f32, v: f32) -> Option<Target> {
+    let (orig, dir) = camera.generate_ray(u, v);
+    let (idx, isect) = closest_hit_bvh(bvh?, objects, &orig, &dir);
+    Some(Target { index: idx?, normal: isect.normal })
+}
+
+// Todo lo que cambia al editar. `scene_indices[i]` es el índice en `scene.objects` del cubo
+// `i` de `objects` (la copia de render que recorre `bvh`).
+pub struct EditTarget<'a> {
+    pub scene: &'a mut Scene,
+    pub scene_indices: &'a mut Vec<usize>,
+    pub objects: &'a mut Vec<Cube>,
+    pub bvh: &'a mut BVH,
+}
+
+impl EditTarget<'_> {
+    // Bloque de una celda con el material de la paleta pegado a la cara `t.normal` del bloque
+    // apuntado: encima o debajo (en la capa siguiente o la anterior) o en la celda vecina de la
+    // grilla (en la misma capa). None si choca con otro bloque o con la cámara.
+    pub fn block_against(&self, t: &Target, material: MaterialId, eye: &glm::Vec3) -> Option<(Cube, usize)> {
+        let hit = &self.objects[t.index];
+        let grid = &self.scene.grid;
+        let half = glm::vec3(0.5, 0.5, 0.5) * grid.cell_size;
+        let vertical = t.normal.y.abs() > 0.5;
+        let center = if vertical {
+            hit.center + glm::vec3(0.0, t.normal.y.signum() * (hit.half_size.y + half.y), 0.0)
+        } else {
+            hit.center + grid.cell_step(&t.normal)
+        };
+
+        let cube = self.block_at(center, material, eye)?;
+        let layer = self.scene.object_layers[self.scene_indices[t.index]];
+        // no hay capas por debajo de la 1
+        let layer = match (vertical, t.normal.y > 0.0) {
+            (false, _) => layer,
+            (true, true) => layer + 1,
+            (true, false) => layer.saturating_sub(1).max(1),
+        };
+        Some((cube, layer))
+    }
+
+    // Primer bloque cuando no hay ninguno (escena o capa vacía): apoyado en el piso de la capa
+    // `layer` (las capas se apilan de a una celda desde y = 0), en la celda donde el rayo corta
+    // ese piso. None si el rayo no lo corta o si choca con la cámara.
+    pub fn block_on_ground(&self, orig: &glm::Vec3, dir: &glm::Vec3, layer: usize, material: MaterialId, eye: &glm::Vec3) -> Option<(Cube, usize)> {
+        let grid = &self.scene.grid;
+        let layer = layer.max(1);
+        let floor = (layer - 1) as f32 * grid.cell_size;
+        let t = (floor - orig.y) / dir.y;
+        if !t.is_finite() || t <= 0.0 { return None; }
+        let mut center = grid.snap(&(orig + dir * t));
+        center.y = floor + 0.5 * grid.cell_size;
+        Some((self.block_at(center, material, eye)?, layer))
+    }
+
+    // Bloque de una celda centrado en `center`; None si choca con otro bloque o con la cámara
+    fn block_at(&self, center: glm::Vec3, material: MaterialId, eye: &glm::Vec3) -> Option<Cube> {
+        let cell_size = self.scene.grid.cell_size;
+        let half = glm::vec3(0.5, 0.5, 0.5) * cell_size;
+        let (min, max) = (center - half, center + half);
+        let inside = |p: &glm::Vec3| (0..3).all(|a| p[a] > min[a] && p[a] < max[a]);
+        let margin = glm::vec3(1.0, 1.0, 1.0) * (OVERLAP_MARGIN * cell_size);
+        if inside(eye) || !overlapping_bvh(self.bvh, self.objects, &(min + margin), &(max - margin), |_| true).is_empty() {
+            return None;
+        }
+        Some(Cube { center, half_size: half, rot_y: 0.0, material: material.material(), top_material: None, radius: glm::length(&half) })
+    }
+
+    // Copia del cubo `index` tal como está en la escena, con su capa
+    pub fn block(&self, index: usize) -> (Cube, usize) {
+        let si = self.scene_indices[index];
+        (self.scene.objects[si].clone(), self.scene.object_layers[si])
+    }
+
+    // Agrega `cube` a la capa `layer` (creándola si es nueva); devuelve su índice en la copia de render
+    pub fn place(&mut self, cube: Cube, layer: usize) -> usize {
+        while self.scene.layer_names.len() < layer {
+            let n = self.scene.layer_names.len() + 1;
+            self.scene.layer_names.push(format!("Capa {}", n));
+        }
+        let (cell, _) = self.scene.grid.cell_at(&cube.center);
+        self.bvh.push(self.objects, prepare_cube(&cube));
+        self.scene.objects.push(cube);
+        self.scene.object_layers.push(layer);
+        self.scene.object_cells.push(cell);
+        self.scene_indices.push(self.scene.objects.len() - 1);
+        self.objects.len() - 1
+    }
+
+    // Quita el cubo `index` de la copia de render (y de la escena); devuelve el cubo y su capa.
+    // Como en `Vec::swap_remove`, el último cubo de cada lista ocupa el lugar del quitado.
+    pub fn remove(&mut self, index: usize) -> (Cube, usize) {
+        self.bvh.swap_remove(self.objects, index);
+        let si = self.scene_indices.swap_remove(index);
+        let last = self.scene.objects.len() - 1;
+        if si != last
+            && let Some(moved) = self.scene_indices.iter_mut().find(|i| **i == last)
+        {
+            *moved = si;
+        }
+        self.scene.object_cells.swap_remove(si);
+        let layer = self.scene.object_layers.swap_remove(si);
+        (self.scene.objects.swap_remove(si), layer)
+    }
+
+    // Cambia el material del cubo `index`; devuelve el material y la cara superior anteriores
+    pub fn set_material(&mut self, index: usize, material: Material, top: Option<Material>) -> (Material, Option<Material>) {
+        let cube = &mut self.scene.objects[self.scene_indices[index]];
+        let old_material = std::mem::replace(&mut cube.material, material);
+        let old_top = std::mem::replace(&mut cube.top_material, top);
+        self.objects[index] = prepare_cube(cube);
+        (old_material, old_top)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bvh::{BuildOptions, build_bvh_with};
+
+    // Escena parseada con su copia de render y su BVH, como las arma main (también la usan
+    // los tests de history.rs)
+    pub(crate) struct EditFixture {
+        scene: Scene,
+        scene_indices: Vec<usize>,
+        objects: Vec<Cube>,
+        bvh: BVH,
+    }
+
+    impl EditFixture {
+        pub(crate) fn parse(text: &str) -> Self {
+            let scene = Scene::parse(text).unwrap();
+            let scene_indices = (0..scene.objects.len()).collect();
+            let objects: Vec<Cube> = scene.objects.iter().map(prepare_cube).collect();
+            let bvh = build_bvh_with(&objects, BuildOptions::default());
+            EditFixture { scene, scene_indices, objects, bvh }
+        }
+
+        pub(crate) fn target(&mut self) -> EditTarget<'_> {
+            EditTarget { scene: &mut self.scene, scene_indices: &mut self.scene_indices, objects: &mut self.objects, bvh: &mut self.bvh }
+        }
+    }
+
+    const TWO_LAYERS: &str = r#"
+[[block]]
+key = "G"
+material = "grass"
+
+[[layer]]
+name = "suelo"
+y = 0.5
+rows = ["G"]
+
+[[layer]]
+name = "arriba"
+y = 1.5
+rows = [".G"]
+"#;
+
+    #[test]
+    fn placed_block_takes_the_neighbouring_layer() {
+        let mut fixture = EditFixture::parse(TWO_LAYERS);
+        let mut target = fixture.target();
+        let eye = glm::vec3(-10.0, 10.0, -10.0);
+        let layer_against = |target: &EditTarget, index: usize, normal: glm::Vec3| {
+            target.block_against(&Target { index, normal }, MaterialId::Stone, &eye).map(|(_, layer)| layer)
+        };
+        let up = glm::vec3(0.0, 1.0, 0.0);
+        // sobre el suelo: capa 2; al costado: la misma; debajo de la capa 1 no hay otra
+        assert_eq!(layer_against(&target, 0, up), Some(2));
+        assert_eq!(layer_against(&target, 0, glm::vec3(0.0, 0.0, 1.0)), Some(1));
+        assert_eq!(layer_against(&target, 0, -up), Some(1));
+        assert_eq!(layer_against(&target, 1, -up), Some(1));
+        // encima del bloque de la capa 2 va la capa 3, que todavía no existe
+        let (cube, layer) = target.block_against(&Target { index: 1, normal: up }, MaterialId::Stone, &eye).unwrap();
+        assert_eq!(layer, 3);
+        assert_eq!(cube.center, glm::vec3(1.0, 2.5, 0.0));
+        let index = target.place(cube, layer);
+        assert_eq!(target.scene.layer_names, vec!["suelo".to_string(), "arriba".to_string(), "Capa 3".to_string()]);
+        assert_eq!(target.block(index).1, 3);
+        assert_eq!(target.scene.object_cells[target.scene_indices[index]], [2, 1]);
+    }
+
+    #[test]
+    fn first_block_goes_on_the_ground() {
+        let mut fixture = EditFixture::parse("[scene]\nname = \"vacía\"\n");
+        let mut target = fixture.target();
+        let (orig, eye) = (glm::vec3(2.2, 5.0, 3.7), glm::vec3(-10.0, 10.0, -10.0));
+        let down = glm::vec3(0.0, -1.0, 0.0);
+        assert!(target.block_on_ground(&orig, &-down, 1, MaterialId::Stone, &eye).is_none());
+        // en la capa 2 el piso está a una celda de altura
+        let (cube, layer) = target.block_on_ground(&orig, &down, 2, MaterialId::Stone, &eye).unwrap();
+        assert_eq!((cube.center, layer), (glm::vec3(2.0, 1.5, 4.0), 2));
+        let (cube, layer) = target.block_on_ground(&orig, &down, 1, MaterialId::Stone, &eye).unwrap();
+        assert_eq!((cube.center, layer), (glm::vec3(2.0, 0.5, 4.0), 1));
+        let index = target.place(cube, layer);
+        assert_eq!(target.scene.layer_names, vec!["Capa 1".to_string()]);
+        assert_eq!(target.scene.object_cells[target.scene_indices[index]], [3, 5]);
+        // ya ocupada
+        assert!(target.block_on_ground(&orig, &down, 1, MaterialId::Stone, &eye).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+use std::f32::consts::PI;
+use nalgebra_glm as glm;
+
+// Mapa de entorno HDR equirectangular (.hdr Radiance RGBE): reemplaza al cielo procedural
+// como fondo y en los reflejos, y da la luz ambiente según la normal.
+pub struct EnvMap {
+    width: usize,
+    height: usize,
+    // radiancia lineal por pixel, fila 0 = cénit
+    pixels: Vec<glm::Vec3>,
+    // giro alrededor del eje y (radianes) y multiplicador de la radiancia
+    rotation: f32,
+    intensity: f32,
+    // irradiancia difusa proyectada en armónicos esféricos de orden 2
+    sh: [glm::Vec3; 9],
+}
+
+// Máximo de muestras por eje al proyectar en armónicos esféricos
+const SH_MAX_SAMPLES: usize = 256;
+
+// Base de armónicos esféricos reales hasta l = 2
+fn sh_basis(d: &glm::Vec3) -> [f32; 9] {
+    [
+        0.282_095,
+        0.488_603 * d.y,
+        0.488_603 * d.z,
+        0.488_603 * d.x,
+        1.092_548 * d.x * d.y,
+        1.092_548 * d.y * d.z,
+        0.315_392 * (3.0 * d.z * d.z - 1.0),
+        1.092_548 * d.x * d.z,
+        0.546_274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+impl EnvMap {
+    pub fn load(path: &str, rotation_deg: f32, intensity: f32) -> Result<EnvMap, String> {
+        let img = image::open(path).map_err(|e| format!("failed to open {:?}: {}", path, e))?;
+        let rgb = img.to_rgb32f();
+        if rgb.width() == 0 || rgb.height() == 0 {
+            return Err(format!("failed to open {:?}: empty image", path));
+        }
+        let pixels = rgb.pixels().map(|p| glm::vec3(p[0], p[1], p[2])).collect();
+        let mut env = EnvMap {
+            width: rgb.width() as usize,
+            height: rgb.height() as usize,
+            pixels,
+            rotation: rotation_deg.to_radians(),
+            intensity,
+            sh: [glm::vec3(0.0, 0.0, 0.0); 9],
+        };
+        env.sh = env.project_irradiance();
+        Ok(env)
+    }
+
+    // Dirección del centro del texel (u, v) en [0, 1], teniendo en cuenta el giro
+    fn direction(&self, u: f32, v: f32) -> glm::Vec3 {
+        let theta = v * PI;
+        let phi = (u - 0.5) * 2.0 * PI - self.rotation;
+        glm::vec3(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+    }
+
+    fn texel(&self, x: usize, y: usize) -> glm::Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    // Radiancia en la dirección `dir` (bilineal; se repite en u y se limita en v)
+    pub fn sample(&self, dir: &glm::Vec3) -> glm::Vec3 {
+        let phi = dir.x.atan2(-dir.z) + self.rotation;
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = dir.y.clamp(-1.0, 1.0).acos() / PI;
+        let fx = u * self.width as f32 - 0.5;
+        let fy = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let tx = fx - x0;
+        let ty = fy - y0;
+        let xa = (x0 as i64).rem_euclid(self.width as i64) as usize;
+        let xb = (xa + 1) % self.width;
+        let ya = y0 as usize;
+        let yb = (ya + 1).min(self.height - 1);
+        let top = glm::mix(&self.texel(xa, ya), &self.texel(xb, ya), tx);
+        let bottom = glm::mix(&self.texel(xa, yb), &self.texel(xb, yb), tx);
+        glm::mix(&top, &bottom, ty) * self.intensity
+    }
+
+    // Radiancia media ponderada por el coseno alrededor de `normal` (irradiancia / pi):
+    // lo que refleja una superficie blanca difusa iluminada solo por el entorno
+    pub fn irradiance(&self, normal: &glm::Vec3) -> glm::Vec3 {
+        let basis = sh_basis(normal);
+        let mut e = glm::vec3(0.0, 0.0, 0.0);
+        for (c, b) in self.sh.iter().zip(basis.iter()) {
+            e += c * *b;
+        }
+        (e / PI).map(|c| c.max(0.0)) * self.intensity
+    }
+
+    // Proyecta la radiancia en armónicos esféricos y aplica la convolución con el coseno
+    // (Ramamoorthi y Hanrahan). Los mapas grandes se submuestrean.
+    fn project_irradiance(&self) -> [glm::Vec3; 9] {
+        let step_x = self.width.div_ceil(SH_MAX_SAMPLES).max(1);
+        let step_y = self.height.div_ceil(SH_MAX_SAMPLES / 2).max(1);
+        let cols = self.width.div_ceil(step_x);
+        let rows = self.height.div_ceil(step_y);
+        let d_theta = PI / rows as f32;
+        let d_phi = 2.0 * PI / cols as f32;
+        let mut coeffs = [glm::vec3(0.0, 0.0, 0.0); 9];
+        for j in 0..rows {
+            let v = (j as f32 + 0.5) / rows as f32;
+            let solid_angle = (v * PI).sin() * d_theta * d_phi;
+            let y = ((v * self.height as f32) as usize).min(self.height - 1);
+            for i in 0..cols {
+                let u = (i as f32 + 0.5) / cols as f32;
+                let x = ((u * self.width as f32) as usize).min(self.width - 1);
+                let radiance = self.texel(x, y);
+                let basis = sh_basis(&self.direction(u, v));
+                for (c, b) in coeffs.iter_mut().zip(basis.iter()) {
+                    *c += radiance * (*b * solid_angle);
+                }
+            }
+        }
+        // factores de la convolución con el lóbulo del coseno por banda
+        let band = [PI, 2.0 * PI / 3.0, 2.0 * PI / 3.0, 2.0 * PI / 3.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0];
+        for (c, a) in coeffs.iter_mut().zip(band.iter()) {
+            *c *= *a;
+        }
+        coeffs
+    }
+}
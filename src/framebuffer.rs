@@ -1,19 +1,20 @@
 use raylib::prelude::*;
 use raylib::prelude::Texture2D;
-// imports relacionados con escritura de archivos fueron removidos
+use image::RgbaImage;
+use crate::color::Color;
 
-// Wrapper sencillo de framebuffer sobre raylib Image
+// Framebuffer en memoria; raylib solo se usa al presentar en ventana
 pub struct Framebuffer {
     width: u32,
     height: u32,
-    color_buffer: Image,
+    color_buffer: Vec<Color>,
     current_color: Color,
     cached_texture: Option<Texture2D>,
 }
 
 impl Framebuffer {
     pub fn new(width: u32, height: u32, background: Color) -> Self {
-        let color_buffer = Image::gen_image_color(width as i32, height as i32, background);
+        let color_buffer = vec![background; (width * height) as usize];
         Framebuffer {
             width,
             height,
@@ -27,11 +28,7 @@ impl Framebuffer {
     pub fn height(&self) -> u32 { self.height }
 
     pub fn clear(&mut self, color: Color) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                Image::draw_pixel(&mut self.color_buffer, x as i32, y as i32, color);
-            }
-        }
+        self.color_buffer.fill(color);
     }
 
     pub fn set_current_color(&mut self, color: Color) {
@@ -40,17 +37,42 @@ impl Framebuffer {
 
     pub fn set_pixel(&mut self, x: u32, y: u32) {
         if x < self.width && y < self.height {
-            Image::draw_pixel(&mut self.color_buffer, x as i32, y as i32, self.current_color);
+            self.color_buffer[(y * self.width + x) as usize] = self.current_color;
         }
     }
 
-    pub fn present(&mut self, window: &mut RaylibHandle, thread: &RaylibThread, scale: f32) {
-        if let Ok(texture) = window.load_texture_from_image(thread, &self.color_buffer) {
+    // Bytes RGBA contiguos, en el orden que esperan raylib e image
+    fn rgba_bytes(&self) -> Vec<u8> {
+        self.color_buffer.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect()
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_raw(self.width, self.height, self.rgba_bytes()).expect("buffer size matches dimensions")
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        self.to_image().save_with_format(path, image::ImageFormat::Png).map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+
+    // Muestra el framebuffer escalado; `overlay` son líneas de texto en un panel arriba a la izquierda
+    pub fn present(&mut self, window: &mut RaylibHandle, thread: &RaylibThread, scale: f32, overlay: &[String]) {
+        if self.cached_texture.is_none() {
+            let blank = Image::gen_image_color(self.width as i32, self.height as i32, raylib::prelude::Color::BLACK);
+            self.cached_texture = window.load_texture_from_image(thread, &blank).ok();
+        }
+        let bytes = self.rgba_bytes();
+        const FONT: i32 = 18;
+        let overlay_width = overlay.iter().map(|l| window.measure_text(l, FONT)).max().unwrap_or(0);
+        if let Some(texture) = self.cached_texture.as_mut() {
+            if texture.update_texture(&bytes).is_err() { return; }
             let mut d = window.begin_drawing(thread);
-            d.draw_texture_ex(&texture, Vector2::new(0.0, 0.0), 0.0, scale, Color::WHITE);
+            d.draw_texture_ex(&*texture, Vector2::new(0.0, 0.0), 0.0, scale, raylib::prelude::Color::WHITE);
+            if !overlay.is_empty() {
+                d.draw_rectangle(8, 8, overlay_width + 16, overlay.len() as i32 * (FONT + 4) + 12, raylib::prelude::Color::new(0, 0, 0, 170));
+                for (i, line) in overlay.iter().enumerate() {
+                    d.draw_text(line, 16, 14 + i as i32 * (FONT + 4), FONT, raylib::prelude::Color::WHITE);
+                }
+            }
         }
     }
-
-    #[allow(dead_code)]
-    pub fn image(&self) -> &Image { &self.color_buffer }
 }
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use crate::bvh::overlapping_bvh;
+use crate::cube::Cube;
+use crate::editor::EditTarget;
+
+// Historial de ediciones para deshacer (Ctrl+Z) y rehacer (Ctrl+Y). Cada edición guarda el
+// cubo completo, así se puede invertir aunque los índices cambien con los `swap_remove`: el
+// cubo se vuelve a encontrar por su caja (centro y medio tamaño) usando el BVH.
+
+// Una edición reversible de la lista de cubos
+#[derive(Clone)]
+pub enum Edit {
+    // cubo agregado o quitado, con su capa (1-based)
+    Place(Cube, usize),
+    Remove(Cube, usize),
+    // cubo con el material nuevo y el mismo cubo como estaba antes
+    Paint { cube: Cube, before: Box<Cube> },
+}
+
+impl Edit {
+    // Edición que deshace esta
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Place(cube, layer) => Edit::Remove(cube.clone(), *layer),
+            Edit::Remove(cube, layer) => Edit::Place(cube.clone(), *layer),
+            Edit::Paint { cube, before } => Edit::Paint { cube: (**before).clone(), before: Box::new(cube.clone()) },
+        }
+    }
+}
+
+impl EditTarget<'_> {
+    // Índice en la copia de render del cubo con la misma caja que `cube`. Si hay varios en la
+    // misma caja se prefiere el que tiene sus materiales (se comparan en la escena, porque la
+    // copia de render puede cambiar el material de los lados).
+    fn find(&self, cube: &Cube) -> Option<usize> {
+        let same = |c: &Cube| c.center == cube.center && c.half_size == cube.half_size;
+        let found = overlapping_bvh(self.bvh, self.objects, &cube.center, &cube.center, same);
+        let same_materials = |&&i: &&usize| {
+            let c = &self.scene.objects[self.scene_indices[i]];
+            c.material.id == cube.material.id && c.top_material.as_ref().map(|m| m.id) == cube.top_material.as_ref().map(|m| m.id)
+        };
+        found.iter().find(same_materials).or(found.first()).copied()
+    }
+
+    // Aplica `edit`; false si el cubo a quitar o pintar ya no está
+    pub fn apply(&mut self, edit: &Edit) -> bool {
+        match edit {
+            Edit::Place(cube, layer) => {
+                self.place(cube.clone(), *layer);
+            }
+            Edit::Remove(cube, _) => {
+                let Some(index) = self.find(cube) else { return false };
+                self.remove(index);
+            }
+            Edit::Paint { cube, .. } => {
+                let Some(index) = self.find(cube) else { return false };
+                self.set_material(index, cube.material.clone(), cube.top_material.clone());
+            }
+        }
+        true
+    }
+}
+
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    // máximo de ediciones guardadas; las más viejas se descartan
+    limit: usize,
+}
+
+impl History {
+    pub const DEFAULT_LIMIT: usize = 256;
+
+    pub fn new(limit: usize) -> Self {
+        History { undo: VecDeque::new(), redo: Vec::new(), limit }
+    }
+
+    // Aplica una edición nueva y la anota; se pierde lo que había para rehacer
+    pub fn perform(&mut self, target: &mut EditTarget, edit: Edit) -> bool {
+        if !target.apply(&edit) { return false; }
+        self.redo.clear();
+        if self.limit == 0 { return true; }
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+        true
+    }
+
+    // Deshace la última edición; false si no hay ninguna o no se pudo aplicar (y entonces
+    // queda donde estaba)
+    pub fn undo(&mut self, target: &mut EditTarget) -> bool {
+        let Some(edit) = self.undo.pop_back() else { return false };
+        if !target.apply(&edit.inverse()) {
+            self.undo.push_back(edit);
+            return false;
+        }
+        self.redo.push(edit);
+        true
+    }
+
+    // Rehace la última edición deshecha; false si no hay ninguna o no se pudo aplicar
+    pub fn redo(&mut self, target: &mut EditTarget) -> bool {
+        let Some(edit) = self.redo.pop() else { return false };
+        if !target.apply(&edit) {
+            self.redo.push(edit);
+            return false;
+        }
+        self.undo.push_back(edit);
+        true
+    }
+
+    // Línea de estado para el panel en pantalla
+    pub fn status(&self) -> String {
+        format!("history: {} undo, {} redo", self.undo.len(), self.redo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Target;
+    use crate::editor::tests::EditFixture;
+    use crate::materials::MaterialId;
+    use nalgebra_glm as glm;
+
+    // El bloque de piedra ocupa la misma caja que el pasto de la celda [1, 1]
+    const SHARED_BOX: &str = r#"
+[[block]]
+key = "G"
+material = "grass"
+
+[[layer]]
+name = "suelo"
+y = 0.5
+rows = ["GG"]
+
+[[cubes]]
+layer = 1
+material = "stone"
+half_size = [0.5, 0.5, 0.5]
+y = 0.5
+cells = [[1, 1]]
+"#;
+
+    // Cubos de la escena (caja, materiales y capa) sin importar el orden, y la copia de render
+    // tiene que coincidir con la escena
+    fn snapshot(target: &EditTarget) -> Vec<String> {
+        assert_eq!(target.objects.len(), target.scene.objects.len());
+        for (obj, &si) in target.objects.iter().zip(target.scene_indices.iter()) {
+            assert_eq!(obj.center, target.scene.objects[si].center);
+        }
+        let mut cubes: Vec<String> = target.scene.objects.iter().zip(&target.scene.object_layers)
+            .map(|(c, layer)| format!("{:?} {:?} {:?} {:?} {}", c.center, c.half_size, c.material.id, c.top_material.as_ref().map(|m| m.id), layer))
+            .collect();
+        cubes.sort();
+        cubes
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_cube_list() {
+        let mut fixture = EditFixture::parse(SHARED_BOX);
+        let mut target = fixture.target();
+        let mut history = History::new(History::DEFAULT_LIMIT);
+        let initial = snapshot(&target);
+
+        // quitar la piedra que comparte caja con el pasto, pintar el otro pasto y apilar uno encima
+        let (stone, layer) = target.block(2);
+        assert_eq!(stone.material.id, MaterialId::Stone);
+        assert_eq!(stone.center, target.block(0).0.center);
+        assert!(history.perform(&mut target, Edit::Remove(stone, layer)));
+        let (before, _) = target.block(1);
+        let mut painted = before.clone();
+        painted.material = MaterialId::Gold.material();
+        assert!(history.perform(&mut target, Edit::Paint { cube: painted, before: Box::new(before) }));
+        let eye = glm::vec3(-10.0, 10.0, -10.0);
+        let (cube, layer) = target.block_against(&Target { index: 0, normal: glm::vec3(0.0, 1.0, 0.0) }, MaterialId::Wood, &eye).unwrap();
+        assert!(history.perform(&mut target, Edit::Place(cube, layer)));
+        let edited = snapshot(&target);
+        assert_ne!(edited, initial);
+
+        for _ in 0..2 {
+            for _ in 0..3 {
+                assert!(history.undo(&mut target));
+            }
+            assert!(!history.undo(&mut target));
+            assert_eq!(snapshot(&target), initial);
+            for _ in 0..3 {
+                assert!(history.redo(&mut target));
+            }
+            assert!(!history.redo(&mut target));
+            assert_eq!(snapshot(&target), edited);
+        }
+    }
+
+    #[test]
+    fn failed_undo_keeps_the_edit() {
+        let mut fixture = EditFixture::parse(SHARED_BOX);
+        let mut target = fixture.target();
+        let mut history = History::new(History::DEFAULT_LIMIT);
+
+        let (before, _) = target.block(1);
+        let mut painted = before.clone();
+        painted.material = MaterialId::Gold.material();
+        assert!(history.perform(&mut target, Edit::Paint { cube: painted, before: Box::new(before) }));
+        // el bloque pintado desaparece por fuera del historial: no hay nada que despintar
+        target.remove(1);
+        let after = snapshot(&target);
+        assert!(!history.undo(&mut target));
+        assert_eq!(history.status(), "history: 1 undo, 0 redo");
+        assert_eq!(snapshot(&target), after);
+    }
+}
@@ -0,0 +1,58 @@
+use nalgebra_glm as glm;
+
+use crate::bvh::closest_hit_bvh;
+use crate::camera::Camera;
+use crate::ray_intersect::FaceId;
+use crate::render::RenderContext;
+use crate::scene::Scene;
+
+// Inspección de bloques con el mouse: se lanza un rayo por el cursor a través del BVH y se
+// muestra de dónde sale el cubo alcanzado en la descripción de la escena.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockInfo {
+    pub center: glm::Vec3,
+    // capa (1-based) y su nombre
+    pub layer: usize,
+    pub layer_name: String,
+    // [columna, fila] 1-based en la grilla de la capa
+    pub cell: [i32; 2],
+    pub material: &'static str,
+    pub texture: Option<String>,
+    pub face: FaceId,
+    pub distance: f32,
+}
+
+// Cubo bajo el punto (u, v) de la imagen (en [0, 1] desde la esquina superior izquierda).
+// `scene_indices[i]` es el índice en `scene.objects` del cubo `i` de `ctx.objects`.
+pub fn pick(ctx: &RenderContext, scene: &Scene, scene_indices: &[usize], camera: &Camera, u: f32, v: f32) -> Option<BlockInfo> {
+    let bvh = ctx.bvh?;
+    let (orig, dir) = camera.generate_ray(u, v);
+    let (idx, isect) = closest_hit_bvh(bvh, ctx.objects, &orig, &dir);
+    let idx = idx?;
+    let si = *scene_indices.get(idx)?;
+    let layer = scene.object_layers[si];
+    Some(BlockInfo {
+        center: ctx.objects[idx].center,
+        layer,
+        layer_name: scene.layer_names.get(layer.wrapping_sub(1)).cloned().unwrap_or_default(),
+        cell: scene.object_cells[si],
+        material: isect.material.id.name(),
+        texture: isect.material.face_texture(isect.face).map(str::to_string),
+        face: isect.face,
+        distance: isect.distance,
+    })
+}
+
+impl BlockInfo {
+    // Líneas de texto para el panel en pantalla
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("center ({:.2}, {:.2}, {:.2})  dist {:.1}", self.center.x, self.center.y, self.center.z, self.distance),
+            format!("layer {} ({})", self.layer, self.layer_name),
+            format!("grid col {}, row {}", self.cell[0], self.cell[1]),
+            format!("material {}", self.material),
+            format!("texture {}", self.texture.as_deref().unwrap_or("-")),
+            format!("face {}", self.face.name()),
+        ]
+    }
+}
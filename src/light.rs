@@ -0,0 +1,93 @@
+use nalgebra_glm as glm;
+
+use crate::cube::Cube;
+
+// Fuentes de luz de la escena. Las direcciones se guardan normalizadas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    // Luz en el infinito (el sol); `direction` apunta hacia la luz
+    Directional { direction: glm::Vec3, color: glm::Vec3, intensity: f32 },
+    // Luz puntual con caída cuadrática que se anula suavemente en `range`
+    Point { position: glm::Vec3, color: glm::Vec3, intensity: f32, range: f32 },
+    // Foco: como la puntual pero limitada a un cono alrededor de `direction` (hacia donde apunta).
+    // Entre `cos_inner` y `cos_outer` la intensidad baja suavemente a cero.
+    Spot { position: glm::Vec3, direction: glm::Vec3, color: glm::Vec3, intensity: f32, range: f32, cos_inner: f32, cos_outer: f32 },
+    // Caja que emite luz (un bloque emisivo). Se ilumina desde el punto más cercano de su
+    // superficie, así los vecinos reciben luz pareja y la caída es suave.
+    Area { center: glm::Vec3, half_size: glm::Vec3, color: glm::Vec3, intensity: f32, range: f32 },
+}
+
+// Atenuación por distancia: inversa del cuadrado con ventana suave hasta `range`
+fn falloff(dist: f32, range: f32) -> f32 {
+    let ratio = (dist / range.max(1e-3)).powi(4);
+    let window = (1.0 - ratio).clamp(0.0, 1.0);
+    window * window / (1.0 + dist * dist)
+}
+
+impl Light {
+    pub fn spot(position: glm::Vec3, direction: glm::Vec3, color: glm::Vec3, intensity: f32, range: f32, inner_deg: f32, outer_deg: f32) -> Light {
+        let outer = outer_deg.max(inner_deg);
+        Light::Spot {
+            position,
+            direction: glm::normalize(&direction),
+            color,
+            intensity,
+            range,
+            cos_inner: inner_deg.to_radians().cos(),
+            cos_outer: outer.to_radians().cos(),
+        }
+    }
+
+    // Luz que llega a `point` sin contar sombras: (dirección hacia la luz, distancia, radiancia).
+    // None si el punto está fuera de su alcance o de su cono.
+    pub fn illuminate(&self, point: &glm::Vec3) -> Option<(glm::Vec3, f32, glm::Vec3)> {
+        match *self {
+            Light::Directional { direction, color, intensity } => Some((direction, f32::INFINITY, color * intensity)),
+            Light::Point { position, color, intensity, range } => {
+                let to_light = position - *point;
+                let dist = glm::length(&to_light);
+                if dist >= range || dist < 1e-5 { return None; }
+                Some((to_light / dist, dist, color * (intensity * falloff(dist, range))))
+            }
+            Light::Spot { position, direction, color, intensity, range, cos_inner, cos_outer } => {
+                let to_light = position - *point;
+                let dist = glm::length(&to_light);
+                if dist >= range || dist < 1e-5 { return None; }
+                let l = to_light / dist;
+                let cos_angle = glm::dot(&-l, &direction);
+                if cos_angle <= cos_outer { return None; }
+                let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-4)).clamp(0.0, 1.0);
+                let cone = t * t * (3.0 - 2.0 * t);
+                Some((l, dist, color * (intensity * falloff(dist, range) * cone)))
+            }
+            Light::Area { center, half_size, color, intensity, range } => {
+                let min = center - half_size;
+                let max = center + half_size;
+                let closest = glm::clamp_vec(point, &min, &max);
+                let to_light = closest - *point;
+                let dist = glm::length(&to_light);
+                if dist >= range || dist < 1e-5 { return None; }
+                Some((to_light / dist, dist, color * (intensity * falloff(dist, range))))
+            }
+        }
+    }
+}
+
+// Alcance de la luz de un bloque emisivo
+const AREA_LIGHT_RANGE: f32 = 4.0;
+
+// Una luz de área por cada cubo con material emisivo (en cualquiera de sus caras)
+pub fn emissive_area_lights(objects: &[Cube]) -> Vec<Light> {
+    objects.iter().filter_map(|o| {
+        let emitter = std::iter::once(&o.material).chain(o.top_material.as_ref())
+            .filter(|m| m.is_emissive())
+            .max_by(|a, b| a.emissive_strength.total_cmp(&b.emissive_strength))?;
+        Some(Light::Area {
+            center: o.center,
+            half_size: o.half_size,
+            color: emitter.emissive.to_linear(),
+            intensity: emitter.emissive_strength,
+            range: AREA_LIGHT_RANGE,
+        })
+    }).collect()
+}
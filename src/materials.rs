@@ -1,17 +1,130 @@
 #![allow(dead_code)]
-use crate::ray_intersect::Material;
-use raylib::prelude::Color;
+use crate::ray_intersect::{Material, FaceTextures};
+use crate::color::Color;
+
+// Identidad de cada material del registro. La lógica especial del shading se basa
+// en los flags del material, nunca en comparar colores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MaterialId {
+    Grass,
+    Dirt,
+    DirtPath,
+    DirtPathSide,
+    Path,
+    Stone,
+    LightGray,
+    Wood,
+    Brick,
+    Water,
+    Glass,
+    Gold,
+    DarkWood,
+    Pillar,
+    Pumpkin,
+    PumpkinTop,
+    PumpkinSide,
+    // material armado a mano, fuera del registro
+    Custom,
+}
+
+impl MaterialId {
+    pub const ALL: [MaterialId; 17] = [
+        MaterialId::Grass,
+        MaterialId::Dirt,
+        MaterialId::DirtPath,
+        MaterialId::DirtPathSide,
+        MaterialId::Path,
+        MaterialId::Stone,
+        MaterialId::LightGray,
+        MaterialId::Wood,
+        MaterialId::Brick,
+        MaterialId::Water,
+        MaterialId::Glass,
+        MaterialId::Gold,
+        MaterialId::DarkWood,
+        MaterialId::Pillar,
+        MaterialId::Pumpkin,
+        MaterialId::PumpkinTop,
+        MaterialId::PumpkinSide,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MaterialId::Grass => "grass",
+            MaterialId::Dirt => "dirt",
+            MaterialId::DirtPath => "dirt_path",
+            MaterialId::DirtPathSide => "dirt_path_side",
+            MaterialId::Path => "path",
+            MaterialId::Stone => "stone",
+            MaterialId::LightGray => "light_gray",
+            MaterialId::Wood => "wood",
+            MaterialId::Brick => "brick",
+            MaterialId::Water => "water",
+            MaterialId::Glass => "glass",
+            MaterialId::Gold => "gold",
+            MaterialId::DarkWood => "dark_wood",
+            MaterialId::Pillar => "pillar",
+            MaterialId::Pumpkin => "pumpkin",
+            MaterialId::PumpkinTop => "pumpkin_top",
+            MaterialId::PumpkinSide => "pumpkin_side",
+            MaterialId::Custom => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MaterialId> {
+        MaterialId::ALL.iter().copied().find(|id| id.name() == name)
+    }
+
+    // Construir el material con su fábrica
+    pub fn material(self) -> Material {
+        match self {
+            MaterialId::Grass => material_grass(),
+            MaterialId::Dirt => material_dirt(),
+            MaterialId::DirtPath => material_dirt_path(),
+            MaterialId::DirtPathSide => material_dirt_path_side(),
+            MaterialId::Path => material_path(),
+            MaterialId::Stone => material_stone(),
+            MaterialId::LightGray => material_light_gray(),
+            MaterialId::Wood => material_wood(),
+            MaterialId::Brick => material_brick(),
+            MaterialId::Water => material_water(),
+            MaterialId::Glass => material_glass(),
+            MaterialId::Gold => material_gold(),
+            MaterialId::DarkWood => material_dark_wood(),
+            MaterialId::Pillar => material_pillar(),
+            MaterialId::Pumpkin => material_pumpkin(),
+            MaterialId::PumpkinTop => material_pumpkin_top(),
+            MaterialId::PumpkinSide => material_pumpkin_side(),
+            MaterialId::Custom => Material::new_color(MaterialId::Custom, Color::new(255, 255, 255, 255)),
+        }
+    }
+}
+
+// Rutas de todas las texturas que usan los materiales del registro
+pub fn registry_textures() -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for id in MaterialId::ALL {
+        let m = id.material();
+        let face = m.face_textures.map(|f| vec![f.top, f.side]).unwrap_or_default();
+        for p in m.texture.into_iter().chain(face) {
+            if !paths.contains(&p) { paths.push(p); }
+        }
+    }
+    paths
+}
 
 pub fn material_grass() -> Material {
-    let mut m = Material::new_color(Color::new(80, 180, 70, 255));
+    let mut m = Material::new_color(MaterialId::Grass, Color::new(80, 180, 70, 255));
+    m.face_textures = Some(FaceTextures { top: "cesped.png".to_string(), side: "cesped_de_lado.png".to_string() });
     m.uv_scale = 1.0;
     m.specular = 0.02;
     m.reflectivity = 0.02;
+    m.roughness = 0.9;
     m
 }
 
 pub fn material_dirt() -> Material {
-    let mut m = Material::new_color(Color::new(120, 85, 55, 255));
+    let mut m = Material::new_color(MaterialId::Dirt, Color::new(120, 85, 55, 255));
     m.uv_scale = 1.0;
     m.specular = 0.04;
     m.reflectivity = 0.00;
@@ -20,7 +133,7 @@ pub fn material_dirt() -> Material {
 }
 
 pub fn material_dirt_path() -> Material {
-    let mut m = Material::with_texture(Color::new(120, 85, 55, 255), "camino.png");
+    let mut m = Material::with_texture(MaterialId::DirtPath, Color::new(120, 85, 55, 255), "camino.png");
     m.uv_scale = 1.0;
     m.specular = 0.04;
     m.reflectivity = 0.00;
@@ -29,7 +142,7 @@ pub fn material_dirt_path() -> Material {
 }
 
 pub fn material_dirt_path_side() -> Material {
-    let mut m = Material::with_texture(Color::new(120, 85, 55, 255), "camino_de_lado.png");
+    let mut m = Material::with_texture(MaterialId::DirtPathSide, Color::new(120, 85, 55, 255), "camino_de_lado.png");
     m.uv_scale = 1.0;
     m.specular = 0.04;
     m.reflectivity = 0.00;
@@ -38,120 +151,143 @@ pub fn material_dirt_path_side() -> Material {
 }
 
 pub fn material_path() -> Material {
-    let mut m = Material::new_color(Color::new(218, 187, 147, 255));
+    let mut m = Material::new_color(MaterialId::Path, Color::new(218, 187, 147, 255));
     m.uv_scale = 1.0;
     m.specular = 0.18;
     m.reflectivity = 0.06;
+    m.roughness = 0.6;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_stone() -> Material {
-    let mut m = Material::new_color(Color::new(0x66, 0x68, 0x66, 255));
+    let mut m = Material::new_color(MaterialId::Stone, Color::new(0x66, 0x68, 0x66, 255));
     m.uv_scale = 1.0;
     m.specular = 0.25;
     m.reflectivity = 0.06;
+    m.roughness = 0.45;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_light_gray() -> Material {
-    let mut m = Material::with_texture(Color::new(0xD4, 0xD3, 0xD5, 255), "pared_gris.png");
+    let mut m = Material::with_texture(MaterialId::LightGray, Color::new(0xD4, 0xD3, 0xD5, 255), "pared_gris.png");
     m.uv_scale = 1.0;
     m.specular = 0.25;
     m.reflectivity = 0.06;
+    m.roughness = 0.5;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_wood() -> Material {
-    let mut m = Material::with_texture(Color::new(160, 115, 70, 255), "oakwood.png");
+    let mut m = Material::with_texture(MaterialId::Wood, Color::new(160, 115, 70, 255), "oakwood.png");
     m.uv_scale = 1.0;
     m.specular = 0.12;
     m.reflectivity = 0.03;
+    m.roughness = 0.7;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_brick() -> Material {
-    let mut m = Material::new_color(Color::new(200, 180, 145, 255));
+    let mut m = Material::new_color(MaterialId::Brick, Color::new(200, 180, 145, 255));
     m.uv_scale = 1.0;
     m.specular = 0.06;
     m.reflectivity = 0.02;
+    m.roughness = 0.8;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_water() -> Material {
-    let mut m = Material::new_color(Color::new(96, 170, 230, 220));
+    let mut m = Material::new_color(MaterialId::Water, Color::new(96, 170, 230, 220));
     m.uv_scale = 1.0;
     m.specular = 0.28;
     m.reflectivity = 0.16;
+    m.roughness = 0.03;
     m.transparency = 0.62;
     m.ior = 1.33;
+    m.water_tint = true;
     m
 }
 
 pub fn material_glass() -> Material {
-    let mut m = Material::new_color(Color::new(220, 235, 255, 150));
+    let mut m = Material::new_color(MaterialId::Glass, Color::new(220, 235, 255, 150));
     m.uv_scale = 1.0;
     m.specular = 0.7;
     m.reflectivity = 0.45;
+    m.roughness = 0.0;
     m.transparency = 0.92;
     m.ior = 1.45;
     m
 }
 
 pub fn material_gold() -> Material {
-    let mut m = Material::new_color(Color::new(255, 200, 64, 255));
+    let mut m = Material::new_color(MaterialId::Gold, Color::new(255, 200, 64, 255));
     m.uv_scale = 1.0;
     m.specular = 0.7;
     m.reflectivity = 0.25;
+    m.roughness = 0.2;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_dark_wood() -> Material {
-    let mut m = Material::with_texture(Color::new(0x7F, 0x66, 0x45, 255), "oakwood.png");
+    let mut m = Material::with_texture(MaterialId::DarkWood, Color::new(0x7F, 0x66, 0x45, 255), "oakwood.png");
     m.uv_scale = 1.0;
     m.specular = 0.08;
     m.reflectivity = 0.00;
     m.transparency = 0.0;
+    m.unlit = true;
     m
 }
 
 pub fn material_pillar() -> Material {
-    let mut m = Material::with_texture(Color::new(0xAF, 0x9D, 0x7B, 255), "pilar.png");
+    let mut m = Material::with_texture(MaterialId::Pillar, Color::new(0xAF, 0x9D, 0x7B, 255), "pilar.png");
     m.uv_scale = 1.0;
     m.specular = 0.28;
     m.reflectivity = 0.05;
+    m.roughness = 0.4;
     m.transparency = 0.0;
     m
 }
 
 pub fn material_pumpkin() -> Material {
-    let mut m = Material::new_color(Color::new(255, 140, 48, 255));
+    let mut m = Material::new_color(MaterialId::Pumpkin, Color::new(255, 140, 48, 255));
     m.uv_scale = 1.0;
     m.specular = 0.22;
     m.reflectivity = 0.06;
+    m.roughness = 0.5;
     m.transparency = 0.0;
+    m.emissive = Color::new(255, 150, 40, 255);
+    m.emissive_strength = 1.5;
     m
 }
 
 pub fn material_pumpkin_top() -> Material {
-    let mut m = Material::with_texture(Color::new(255, 140, 48, 255), "arriba_calabaza.png");
+    let mut m = Material::with_texture(MaterialId::PumpkinTop, Color::new(255, 140, 48, 255), "arriba_calabaza.png");
     m.uv_scale = 1.0;
     m.specular = 0.22;
     m.reflectivity = 0.06;
     m.transparency = 0.0;
+    m.emissive = Color::new(255, 150, 40, 255);
+    m.emissive_strength = 1.5;
     m
 }
 
 pub fn material_pumpkin_side() -> Material {
-    let mut m = Material::with_texture(Color::new(255, 140, 48, 255), "calabaza.png");
+    let mut m = Material::with_texture(MaterialId::PumpkinSide, Color::new(255, 140, 48, 255), "calabaza.png");
     m.uv_scale = 1.0;
     m.specular = 0.22;
     m.reflectivity = 0.06;
     m.transparency = 0.0;
+    m.emissive = Color::new(255, 150, 40, 255);
+    m.emissive_strength = 1.5;
     m
 }
+
+// Buscar una fábrica de material por su nombre (el usado en los archivos de escena)
+pub fn material_by_name(name: &str) -> Option<Material> {
+    MaterialId::from_name(name).map(MaterialId::material)
+}
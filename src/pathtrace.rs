@@ -0,0 +1,224 @@
+use rayon::prelude::*;
+use rand::Rng;
+use nalgebra_glm as glm;
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::light::Light;
+use crate::materials::MaterialId;
+use crate::render::{
+    AREA_SHADOW_MARGIN, EXPOSURE, RenderContext, closest_hit, cosine_sample_hemisphere, is_inner_face,
+    linear_to_color, orthonormal_basis, reflect, refract, sample_material, schlick, shadow_factor, tonemap,
+};
+
+// Trazado de caminos Monte Carlo para imágenes de referencia (solo sin ventana): rebotes
+// difusos con muestreo por coseno, reflejos brillantes según `roughness`, ruleta rusa y
+// estimación de luz directa (NEE) hacia el sol, las demás luces y los bloques emisivos.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathTraceOptions {
+    // muestras por pixel
+    pub samples: u32,
+    // rebotes máximos por camino
+    pub max_bounces: u32,
+}
+
+impl Default for PathTraceOptions {
+    fn default() -> Self {
+        PathTraceOptions { samples: 64, max_bounces: 8 }
+    }
+}
+
+// La ruleta rusa empieza después de estos rebotes
+const RR_MIN_BOUNCES: u32 = 3;
+// Atenuación de la luz que atraviesa el agua (tinte azulado)
+const WATER_TINT: [f32; 3] = [0.9, 0.93, 0.98];
+
+pub fn render(framebuffer: &mut Framebuffer, ctx: &RenderContext, camera: &Camera, options: PathTraceOptions) {
+    let w = framebuffer.width() as usize;
+    let h = framebuffer.height() as usize;
+    let samples = options.samples.max(1);
+    let mut colors = vec![Color::BLACK; w * h];
+    colors.par_iter_mut().enumerate().for_each_init(rand::rng, |rng, (idx, out)| {
+        let mut sum = glm::vec3(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let x = (idx % w) as f32 + rng.random::<f32>();
+            let y = (idx / w) as f32 + rng.random::<f32>();
+            let (orig, dir) = camera.generate_ray(x / w as f32, y / h as f32);
+            sum += radiance(ctx, &orig, &dir, options.max_bounces, rng);
+        }
+        *out = linear_to_color(&tonemap(sum / samples as f32 * EXPOSURE));
+    });
+    for (idx, col) in colors.into_iter().enumerate() {
+        framebuffer.set_current_color(col);
+        framebuffer.set_pixel((idx % w) as u32, (idx / w) as u32);
+    }
+}
+
+// Radiancia (lineal, sin mapear) que llega por el rayo
+fn radiance<R: Rng>(ctx: &RenderContext, orig: &glm::Vec3, dir: &glm::Vec3, max_bounces: u32, rng: &mut R) -> glm::Vec3 {
+    let bias = 1e-3f32;
+    let mut orig = *orig;
+    let mut dir = *dir;
+    let mut throughput = glm::vec3(1.0, 1.0, 1.0);
+    let mut result = glm::vec3(0.0, 0.0, 0.0);
+    let mut medium: Option<MaterialId> = None;
+    // la emisión de un bloque ya se contó con NEE salvo tras un rebote especular
+    let emitters_sampled = ctx.lights.iter().any(|l| matches!(l, Light::Area { .. }));
+    let mut specular_bounce = true;
+
+    for bounce in 0..=max_bounces {
+        let isect = closest_hit(ctx, &orig, &dir);
+        if !isect.is_intersecting {
+            let sky = match ctx.environment {
+                Some(env) => env.sample(&dir),
+                None => ctx.sky.sample(&dir),
+            };
+            result += throughput.component_mul(&sky);
+            break;
+        }
+
+        let material = &isect.material;
+        let base = sample_material(material, isect.uv.0, isect.uv.1, isect.face, ctx.textures).to_linear();
+        if material.unlit {
+            result += throughput.component_mul(&base);
+            break;
+        }
+        if material.is_emissive() && (specular_bounce || !emitters_sampled) {
+            let emission = base.component_mul(&material.emissive.to_linear()) * material.emissive_strength;
+            result += throughput.component_mul(&emission);
+        }
+        if bounce == max_bounces { break; }
+
+        let trans = material.transparency.clamp(0.0, 1.0);
+        if trans > 0.01 && rng.random::<f32>() < trans {
+            // Dieléctrico: reflexión o refracción según Fresnel (una sola rama por camino)
+            if is_inner_face(ctx, &isect, &dir, medium) {
+                orig = isect.point + dir * bias;
+                continue;
+            }
+            let entering = glm::dot(&dir, &isect.normal) < 0.0;
+            let ior = material.ior.max(1.0);
+            let (n, n1, n2, next_medium) = if entering {
+                (isect.normal, 1.0, ior, Some(material.id))
+            } else {
+                (-isect.normal, ior, 1.0, None)
+            };
+            let cos_i = (-glm::dot(&dir, &n)).clamp(0.0, 1.0);
+            let kr = schlick(cos_i, n1, n2);
+            match refract(&dir, &n, n1 / n2) {
+                Some(t) if rng.random::<f32>() >= kr => {
+                    orig = isect.point - n * bias;
+                    dir = t;
+                    medium = next_medium;
+                    if material.water_tint && entering {
+                        throughput = throughput.component_mul(&glm::vec3(WATER_TINT[0], WATER_TINT[1], WATER_TINT[2]));
+                    }
+                }
+                _ => {
+                    orig = isect.point + n * bias;
+                    dir = glm::normalize(&reflect(&dir, &n));
+                }
+            }
+            specular_bounce = true;
+            continue;
+        }
+
+        // Superficie opaca (o la parte opaca de una transparente); la normal mira al rayo
+        let normal = if glm::dot(&dir, &isect.normal) > 0.0 { -isect.normal } else { isect.normal };
+        let refl = material.reflectivity.clamp(0.0, 1.0);
+        if refl > 0.0 && rng.random::<f32>() < refl {
+            // Reflejo brillante: lóbulo de Phong alrededor del espejo, más ancho cuanto más rugoso
+            let mirror = glm::normalize(&reflect(&dir, &normal));
+            let new_dir = glossy_sample(&mirror, material.roughness, rng.random::<f32>(), rng.random::<f32>());
+            if glm::dot(&new_dir, &normal) <= 0.0 { break; }
+            orig = isect.point + normal * bias;
+            dir = new_dir;
+            specular_bounce = true;
+        } else {
+            result += throughput.component_mul(&base.component_mul(&direct_light(ctx, &isect.point, &normal, rng)));
+            // Rebote difuso: con muestreo por coseno el peso es el albedo
+            throughput = throughput.component_mul(&base);
+            orig = isect.point + normal * bias;
+            dir = cosine_sample_hemisphere(&normal, rng.random::<f32>(), rng.random::<f32>());
+            specular_bounce = false;
+        }
+
+        if bounce >= RR_MIN_BOUNCES {
+            let survive = throughput.max().clamp(0.05, 0.95);
+            if rng.random::<f32>() >= survive { break; }
+            throughput /= survive;
+        }
+    }
+    result
+}
+
+// Luz directa sobre una superficie difusa blanca en `point`: las luces puntuales y el sol
+// como en el render interactivo, y los bloques emisivos muestreando un punto de su superficie
+fn direct_light<R: Rng>(ctx: &RenderContext, point: &glm::Vec3, normal: &glm::Vec3, rng: &mut R) -> glm::Vec3 {
+    let mut total = glm::vec3(0.0, 0.0, 0.0);
+    for light in ctx.lights {
+        // sin corte por `range`: los rebotes difusos no cuentan la emisión, así que cada
+        // bloque emisivo debe llegar por aquí a cualquier distancia
+        if let Light::Area { center, half_size, color, intensity, .. } = *light {
+            let (target, light_normal, area) = sample_box_surface(&center, &half_size, rng);
+            let to_light = target - point;
+            let dist = glm::length(&to_light);
+            if dist < 1e-4 { continue; }
+            let l = to_light / dist;
+            let cos_surface = glm::dot(normal, &l);
+            let cos_light = -glm::dot(&light_normal, &l);
+            if cos_surface <= 0.0 || cos_light <= 0.0 { continue; }
+            let shadow = shadow_factor(ctx, point, normal, &l, dist - AREA_SHADOW_MARGIN);
+            if shadow <= 0.0 { continue; }
+            // Lambert (albedo / pi) por la radiancia emitida, con pdf de área 1 / area
+            let geometry = cos_surface * cos_light * area / (dist * dist * std::f32::consts::PI);
+            total += color * (intensity * geometry * shadow);
+            continue;
+        }
+        let Some((l, dist, radiance)) = light.illuminate(point) else { continue };
+        let cos_surface = glm::dot(normal, &l);
+        if cos_surface <= 0.0 { continue; }
+        let shadow = shadow_factor(ctx, point, normal, &l, dist);
+        if shadow <= 0.0 { continue; }
+        total += radiance * (cos_surface * shadow);
+    }
+    total
+}
+
+// Punto uniforme sobre la superficie de la caja: (punto, normal de la cara, área total)
+fn sample_box_surface<R: Rng>(center: &glm::Vec3, half: &glm::Vec3, rng: &mut R) -> (glm::Vec3, glm::Vec3, f32) {
+    // área de una cara perpendicular a cada eje, sobre 4
+    let areas = [half.y * half.z, half.x * half.z, half.x * half.y];
+    let mut pick = rng.random::<f32>() * (areas[0] + areas[1] + areas[2]);
+    let mut axis = 2;
+    for (a, area) in areas.iter().enumerate() {
+        if pick < *area {
+            axis = a;
+            break;
+        }
+        pick -= area;
+    }
+    let side = if rng.random::<bool>() { 1.0 } else { -1.0 };
+    let mut offset = glm::vec3(
+        (rng.random::<f32>() * 2.0 - 1.0) * half.x,
+        (rng.random::<f32>() * 2.0 - 1.0) * half.y,
+        (rng.random::<f32>() * 2.0 - 1.0) * half.z,
+    );
+    offset[axis] = half[axis] * side;
+    let mut normal = glm::vec3(0.0, 0.0, 0.0);
+    normal[axis] = side;
+    (center + offset, normal, 8.0 * (areas[0] + areas[1] + areas[2]))
+}
+
+// Dirección alrededor de `mirror` con un lóbulo de Phong cuyo exponente sale de la rugosidad
+fn glossy_sample(mirror: &glm::Vec3, roughness: f32, r1: f32, r2: f32) -> glm::Vec3 {
+    let alpha = (roughness * roughness).max(1e-4);
+    let exponent = (2.0 / (alpha * alpha) - 2.0).max(0.0);
+    if exponent > 1e5 { return *mirror; }
+    let cos_theta = r1.powf(1.0 / (exponent + 1.0));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * r2;
+    let (t, b) = orthonormal_basis(mirror);
+    glm::normalize(&(t * (sin_theta * phi.cos()) + b * (sin_theta * phi.sin()) + mirror * cos_theta))
+}
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 use nalgebra_glm::Vec3;
-use raylib::prelude::Color;
+use crate::color::Color;
+use crate::materials::MaterialId;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FaceId {
@@ -13,24 +14,75 @@ pub enum FaceId {
     Unknown,
 }
 
+impl FaceId {
+    pub fn name(self) -> &'static str {
+        match self {
+            FaceId::Right => "right",
+            FaceId::Left => "left",
+            FaceId::Top => "top",
+            FaceId::Bottom => "bottom",
+            FaceId::Front => "front",
+            FaceId::Back => "back",
+            FaceId::Unknown => "unknown",
+        }
+    }
+}
+
+// Texturas distintas para la cara superior y las laterales (p. ej. el pasto)
+#[derive(Debug, Clone)]
+pub struct FaceTextures {
+    pub top: String,
+    pub side: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Material {
+    pub id: MaterialId,
     pub diffuse: Color,
     pub texture: Option<String>,
+    pub face_textures: Option<FaceTextures>,
     pub uv_scale: f32,
     pub specular: f32,
     pub reflectivity: f32,
+    // ancho del lóbulo del reflejo (0 = espejo, 1 = casi difuso); lo usa el trazado de caminos
+    pub roughness: f32,
     pub transparency: f32,
     pub ior: f32,
+    // se muestra con su color de textura, sin iluminación
+    pub unlit: bool,
+    // aplica el tinte azulado del agua a lo que se ve a través
+    pub water_tint: bool,
+    // luz propia (calabazas encendidas); se suma tal cual al color
+    pub emissive: Color,
+    pub emissive_strength: f32,
 }
 
 impl Material {
-    pub fn new_color(diffuse: Color) -> Self {
-        Material { diffuse, texture: None, uv_scale: 1.0, specular: 0.0, reflectivity: 0.0, transparency: 0.0, ior: 1.0 }
+    pub fn is_emissive(&self) -> bool {
+        self.emissive_strength > 0.0 && self.emissive != Color::BLACK
+    }
+
+    // Textura que se ve en la cara `face` (la misma elección que hace el render)
+    pub fn face_texture(&self, face: FaceId) -> Option<&str> {
+        if let Some(ref path) = self.texture {
+            return Some(path.as_str());
+        }
+        let faces = self.face_textures.as_ref()?;
+        match face {
+            FaceId::Top => Some(faces.top.as_str()),
+            FaceId::Left | FaceId::Right | FaceId::Front | FaceId::Back => Some(faces.side.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn new_color(id: MaterialId, diffuse: Color) -> Self {
+        Material { id, diffuse, texture: None, face_textures: None, uv_scale: 1.0, specular: 0.0, reflectivity: 0.0, roughness: 0.5, transparency: 0.0, ior: 1.0, unlit: false, water_tint: false, emissive: Color::BLACK, emissive_strength: 0.0 }
     }
 
-    pub fn with_texture(diffuse: Color, texture_path: impl Into<String>) -> Self {
-        Material { diffuse, texture: Some(texture_path.into()), uv_scale: 1.0, specular: 0.0, reflectivity: 0.0, transparency: 0.0, ior: 1.0 }
+    pub fn with_texture(id: MaterialId, diffuse: Color, texture_path: impl Into<String>) -> Self {
+        let mut m = Material::new_color(id, diffuse);
+        m.texture = Some(texture_path.into());
+        m
     }
 }
 
@@ -51,7 +103,7 @@ impl Intersect {
     }
 
     pub fn empty() -> Self {
-        Intersect { distance: 0.0, is_intersecting: false, material: Material::new_color(Color::new(0, 0, 0, 255)), normal: Vec3::new(0.0,0.0,0.0), point: Vec3::new(0.0,0.0,0.0), uv: (0.0,0.0), face: FaceId::Unknown }
+        Intersect { distance: 0.0, is_intersecting: false, material: Material::new_color(MaterialId::Custom, Color::new(0, 0, 0, 255)), normal: Vec3::new(0.0,0.0,0.0), point: Vec3::new(0.0,0.0,0.0), uv: (0.0,0.0), face: FaceId::Unknown }
     }
 }
 
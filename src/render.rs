@@ -0,0 +1,493 @@
+use rayon::prelude::*;
+use rand::Rng;
+use nalgebra_glm as glm;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::ao::{AoMode, ambient_occlusion};
+use crate::bvh::{BVH, find_containing_bvh, intersect_bvh, occluded_bvh};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::envmap::EnvMap;
+use crate::framebuffer::Framebuffer;
+use crate::light::Light;
+use crate::materials::MaterialId;
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::sky::Sky;
+use crate::texture;
+
+// Diagnóstico: impactos primarios acumulados desde la última lectura
+pub static HIT_COUNT: AtomicUsize = AtomicUsize::new(0);
+// Global exposure multiplier (tune to brighten/darken scene)
+pub(crate) const EXPOSURE: f32 = 1.6;
+
+// Todo lo que el trazador necesita de la escena para sombrear un rayo
+pub struct RenderContext<'a> {
+    pub objects: &'a [Cube],
+    pub bvh: Option<&'a BVH>,
+    pub textures: Option<&'a texture::TextureManager>,
+    pub lights: &'a [Light],
+    pub ambient: f32,
+    pub sky: Sky,
+    // mapa de entorno HDR; si está, reemplaza al cielo procedural y da la luz ambiente
+    pub environment: Option<&'a EnvMap>,
+    pub ao: AoMode,
+}
+
+pub(crate) fn reflect(v: &glm::Vec3, n: &glm::Vec3) -> glm::Vec3 {
+    *v - *n * 2.0 * glm::dot(v, n)
+}
+
+// Fondo visto en la dirección `dir` (ya mapeado a [0,1]): el mapa de entorno o el cielo
+fn background(ctx: &RenderContext, dir: &glm::Vec3) -> glm::Vec3 {
+    match ctx.environment {
+        Some(env) => tonemap(env.sample(dir) * EXPOSURE),
+        None => ctx.sky.sample(dir),
+    }
+}
+
+// Término ambiente: uniforme con el cielo procedural, según la normal con un mapa de entorno
+fn ambient_light(ctx: &RenderContext, normal: &glm::Vec3) -> glm::Vec3 {
+    match ctx.environment {
+        Some(env) => env.irradiance(normal) * ctx.ambient,
+        None => glm::vec3(ctx.ambient, ctx.ambient, ctx.ambient),
+    }
+}
+
+pub(crate) fn sample_material(material: &crate::ray_intersect::Material, u: f32, v: f32, face: crate::ray_intersect::FaceId, tx: Option<&texture::TextureManager>) -> Color {
+    if let Some(tm) = tx {
+        if let Some(ref path) = material.texture
+            && let Some(col) = tm.sample(path.as_str(), u * material.uv_scale, v * material.uv_scale)
+        {
+            return col;
+        }
+        if let Some(ref faces) = material.face_textures {
+            let path = match face {
+                crate::ray_intersect::FaceId::Top => Some(&faces.top),
+                crate::ray_intersect::FaceId::Left | crate::ray_intersect::FaceId::Right | crate::ray_intersect::FaceId::Front | crate::ray_intersect::FaceId::Back => Some(&faces.side),
+                _ => None,
+            };
+            if let Some(col) = path.and_then(|p| tm.sample(p.as_str(), u * material.uv_scale, v * material.uv_scale)) {
+                return col;
+            }
+        }
+    }
+
+    material.diffuse
+}
+
+// Límite de rebotes; la refracción necesita margen para entrar y salir del bloque
+const MAX_DEPTH: i32 = 6;
+// Bloques transparentes que puede cruzar un rayo de sombra antes de rendirse
+const MAX_SHADOW_STEPS: usize = 8;
+
+// Reinhard por canal
+pub(crate) fn tonemap(c: glm::Vec3) -> glm::Vec3 {
+    glm::vec3(c.x / (1.0 + c.x), c.y / (1.0 + c.y), c.z / (1.0 + c.z))
+}
+
+pub(crate) fn linear_to_color(c: &glm::Vec3) -> Color {
+    let gamma = 1.0 / 2.2;
+    let r = (c.x.clamp(0.0, 1.0).powf(gamma) * 255.0) as u8;
+    let g = (c.y.clamp(0.0, 1.0).powf(gamma) * 255.0) as u8;
+    let b = (c.z.clamp(0.0, 1.0).powf(gamma) * 255.0) as u8;
+    Color::new(r, g, b, 255)
+}
+
+// Ley de Snell. `n` apunta hacia el lado de donde viene el rayo y `eta` = n1 / n2.
+// Devuelve None en reflexión interna total.
+pub(crate) fn refract(dir: &glm::Vec3, n: &glm::Vec3, eta: f32) -> Option<glm::Vec3> {
+    let cos_i = (-glm::dot(dir, n)).clamp(0.0, 1.0);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    Some(glm::normalize(&(*dir * eta + *n * (eta * cos_i - (1.0 - sin2_t).sqrt()))))
+}
+
+// Aproximación de Schlick a la reflectancia de Fresnel entre medios n1 -> n2
+pub(crate) fn schlick(cos_i: f32, n1: f32, n2: f32) -> f32 {
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    let mut cos = cos_i;
+    if n1 > n2 {
+        // Al salir hacia un medio menos denso se usa el ángulo transmitido
+        let eta = n1 / n2;
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        cos = (1.0 - sin2_t).sqrt();
+    }
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+pub(crate) fn closest_hit(ctx: &RenderContext, orig: &glm::Vec3, dir: &glm::Vec3) -> Intersect {
+    if let Some(b) = ctx.bvh {
+        return intersect_bvh(b, ctx.objects, orig, dir);
+    }
+    let mut intersect = Intersect::empty();
+    let mut zbuffer = f32::INFINITY;
+    for object in ctx.objects {
+        let tmp = object.ray_intersect(orig, dir);
+        if tmp.is_intersecting && tmp.distance < zbuffer {
+            zbuffer = tmp.distance;
+            intersect = tmp;
+        }
+    }
+    intersect
+}
+
+pub(crate) fn occluded<F: Fn(&Cube) -> bool>(ctx: &RenderContext, orig: &glm::Vec3, dir: &glm::Vec3, max_dist: f32, blocks: F) -> bool {
+    if let Some(b) = ctx.bvh {
+        return occluded_bvh(b, ctx.objects, orig, dir, max_dist, blocks);
+    }
+    ctx.objects.iter().any(|o| {
+        if !blocks(o) { return false; }
+        let tmp = o.ray_intersect(orig, dir);
+        tmp.is_intersecting && tmp.distance <= max_dist
+    })
+}
+
+// Un cubo que contiene `point` y cumple `accept`
+pub(crate) fn find_containing<'a, F: Fn(&Cube) -> bool>(ctx: &RenderContext<'a>, point: &glm::Vec3, accept: F) -> Option<&'a Cube> {
+    if let Some(b) = ctx.bvh {
+        return find_containing_bvh(b, ctx.objects, point, accept).map(|i| &ctx.objects[i]);
+    }
+    ctx.objects.iter().find(|o| accept(o) && o.contains(point))
+}
+
+// Dos tangentes perpendiculares a la normal unitaria `n` (Duff et al.)
+pub(crate) fn orthonormal_basis(n: &glm::Vec3) -> (glm::Vec3, glm::Vec3) {
+    let sign = 1.0f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    (glm::vec3(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x), glm::vec3(b, sign + n.y * n.y * a, -n.y))
+}
+
+// Dirección en el hemisferio de `n` con densidad proporcional al coseno; r1, r2 en [0, 1)
+pub(crate) fn cosine_sample_hemisphere(n: &glm::Vec3, r1: f32, r2: f32) -> glm::Vec3 {
+    let (t, bt) = orthonormal_basis(n);
+    let r = r1.sqrt();
+    let phi = 2.0 * std::f32::consts::PI * r2;
+    glm::normalize(&(t * (r * phi.cos()) + bt * (r * phi.sin()) + n * (1.0 - r1).max(0.0).sqrt()))
+}
+
+// Las luces de área se ven desde el punto más cercano de su propia caja: el rayo de sombra se
+// corta un poco antes para no chocar con el bloque que emite
+pub(crate) const AREA_SHADOW_MARGIN: f32 = 2e-3;
+
+// Distancia hasta donde se buscan bloqueos entre un punto y una luz a `dist`
+pub(crate) fn light_shadow_distance(light: &Light, dist: f32) -> f32 {
+    match light {
+        Light::Area { .. } => dist - AREA_SHADOW_MARGIN,
+        _ => dist,
+    }
+}
+
+// Fracción de la luz que llega a `point` desde `light_dir` a distancia `light_dist`
+// (0 = sombra completa). Los opacos bloquean; agua y vidrio solo atenúan según su
+// `transparency` por cada bloque atravesado.
+pub(crate) fn shadow_factor(ctx: &RenderContext, point: &glm::Vec3, normal: &glm::Vec3, light_dir: &glm::Vec3, light_dist: f32) -> f32 {
+    let bias = 1e-3f32;
+    let mut orig = *point + *normal * bias;
+    let mut remaining = light_dist - bias;
+    if occluded(ctx, &orig, light_dir, remaining, |c| c.material.transparency <= 0.01) {
+        return 0.0;
+    }
+    let mut factor = 1.0;
+    for _ in 0..MAX_SHADOW_STEPS {
+        let hit = closest_hit(ctx, &orig, light_dir);
+        if !hit.is_intersecting || hit.distance > remaining {
+            break;
+        }
+        if glm::dot(light_dir, &hit.normal) < 0.0 {
+            factor *= hit.material.transparency.clamp(0.0, 1.0);
+        }
+        orig = hit.point + *light_dir * bias;
+        remaining -= hit.distance + bias;
+    }
+    factor
+}
+
+pub fn cast_ray(ctx: &RenderContext, cam_orig: &glm::Vec3, dir: &glm::Vec3) -> Color {
+    linear_to_color(&cast_ray_linear(ctx, cam_orig, dir))
+}
+
+// Igual que `cast_ray` pero sin cuantizar, para promediar muestras
+pub fn cast_ray_linear(ctx: &RenderContext, cam_orig: &glm::Vec3, dir: &glm::Vec3) -> glm::Vec3 {
+    cast_ray_rec(ctx, cam_orig, dir, 0, None)
+}
+
+// Caras internas entre bloques contiguos del mismo material transparente (agua con agua):
+// no hay cambio de medio y el rayo debe seguir recto.
+pub(crate) fn is_inner_face(ctx: &RenderContext, isect: &Intersect, dir: &glm::Vec3, medium: Option<MaterialId>) -> bool {
+    let id = isect.material.id;
+    if glm::dot(dir, &isect.normal) < 0.0 {
+        return medium == Some(id);
+    }
+    let next = closest_hit(ctx, &(isect.point + *dir * 1e-3), dir);
+    next.is_intersecting
+        && next.material.id == id
+        && (glm::dot(dir, &next.normal) > 0.0 || next.distance < 0.05)
+}
+
+// Devuelve el color ya mapeado a [0,1] en espacio lineal.
+// `medium` es el material transparente por el que viaja el rayo (None = aire).
+fn cast_ray_rec(ctx: &RenderContext, cam_orig: &glm::Vec3, dir: &glm::Vec3, depth: i32, medium: Option<MaterialId>) -> glm::Vec3 {
+    let tx = ctx.textures;
+
+    let isect = closest_hit(ctx, cam_orig, dir);
+    if !isect.is_intersecting {
+        return background(ctx, dir);
+    }
+
+    if depth == 0 {
+        HIT_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let (u, v) = isect.uv;
+    let base_col = sample_material(&isect.material, u, v, isect.face, tx);
+    if isect.material.unlit {
+        return base_col.to_linear();
+    }
+    let base_f = base_col.to_linear();
+
+    let bias = 1e-3f32;
+    let view_dir = glm::normalize(&(-*dir));
+    let spec_strength = isect.material.specular;
+
+    // Suma de todas las luces visibles (Lambert + especular), cada una con su rayo de sombra
+    let mut diffuse = ambient_light(ctx, &isect.normal) * ambient_occlusion(ctx, &isect);
+    let mut specular = glm::vec3(0.0, 0.0, 0.0);
+    for light in ctx.lights {
+        let Some((light_dir, light_dist, radiance)) = light.illuminate(&isect.point) else { continue };
+        let diff = glm::dot(&isect.normal, &light_dir);
+        if diff <= 0.0 { continue; }
+        let shadow = shadow_factor(ctx, &isect.point, &isect.normal, &light_dir, light_shadow_distance(light, light_dist));
+        if shadow <= 0.0 { continue; }
+        diffuse += radiance * (diff * shadow);
+        if spec_strength > 0.0 {
+            let reflect_light = glm::normalize(&reflect(&-light_dir, &isect.normal));
+            let spec_angle = glm::dot(&view_dir, &reflect_light).max(0.0);
+            specular += radiance * (spec_strength * spec_angle.powf(32.0) * shadow);
+        }
+    }
+    let intensity = glm::vec3(diffuse.x.min(1.0), diffuse.y.min(1.0), diffuse.z.min(1.0));
+    let mut final_color = base_f.component_mul(&intensity) + specular;
+    // Emisión modulada por la textura para que el dibujo de la cara siga viéndose
+    if isect.material.is_emissive() {
+        final_color += base_f.component_mul(&isect.material.emissive.to_linear()) * isect.material.emissive_strength;
+    }
+
+    if isect.material.transparency > 0.01 && depth < MAX_DEPTH {
+        let trans = isect.material.transparency.clamp(0.0, 1.0);
+        let id = isect.material.id;
+        let entering = glm::dot(dir, &isect.normal) < 0.0;
+
+        if is_inner_face(ctx, &isect, dir, medium) {
+            return cast_ray_rec(ctx, &(isect.point + *dir * bias), dir, depth, medium);
+        }
+
+        let ior = isect.material.ior.max(1.0);
+        let (n, n1, n2, next_medium) = if entering {
+            (isect.normal, 1.0, ior, Some(id))
+        } else {
+            (-isect.normal, ior, 1.0, None)
+        };
+        let cos_i = (-glm::dot(dir, &n)).clamp(0.0, 1.0);
+        let kr = schlick(cos_i, n1, n2);
+
+        let reflect_dir = glm::normalize(&reflect(dir, &n));
+        let reflected = cast_ray_rec(ctx, &(isect.point + n * bias), &reflect_dir, depth + 1, medium);
+        let transmitted = match refract(dir, &n, n1 / n2) {
+            Some(t) => cast_ray_rec(ctx, &(isect.point - n * bias), &t, depth + 1, next_medium),
+            None => glm::vec3(0.0, 0.0, 0.0),
+        };
+        let mut through = transmitted * (1.0 - kr) + reflected * kr;
+
+        if isect.material.water_tint && entering {
+            through = through * 0.9 + glm::vec3(0.0, 0.03, 0.08) * 0.1;
+        }
+
+        let surface = tonemap(final_color * EXPOSURE);
+        return surface * (1.0 - trans) + through * trans;
+    }
+
+    let refl = isect.material.reflectivity.clamp(0.0, 1.0);
+    if refl > 0.01 && depth < 3 {
+        let reflect_dir = glm::normalize(&reflect(dir, &isect.normal));
+        let reflect_origin = isect.point + isect.normal * bias;
+        let refl_col_f = cast_ray_rec(ctx, &reflect_origin, &reflect_dir, depth + 1, medium);
+        final_color = final_color * (1.0 - refl) + refl_col_f * refl;
+    }
+    tonemap(final_color * EXPOSURE)
+}
+
+// Copia de un cubo de la escena lista para render: los bloques con camino arriba usan la
+// textura lateral de camino
+pub fn prepare_cube(c: &Cube) -> Cube {
+    let mut obj = c.clone();
+    if obj.top_material.as_ref().is_some_and(|tm| tm.id == MaterialId::DirtPath) {
+        obj.material = crate::materials::material_dirt_path_side();
+    }
+    obj
+}
+
+// Escala de render interna por defecto para la ventana interactiva
+pub fn interactive_render_scale(width: u32, height: u32) -> usize {
+    if width > 1200 || height > 1200 { 3 } else { 2 }
+}
+
+pub fn render(framebuffer: &mut Framebuffer, ctx: &RenderContext, camera: &Camera, render_scale: usize) {
+    let render_scale = render_scale.max(1);
+    let w = (framebuffer.width() as usize / render_scale).max(1);
+    let h = (framebuffer.height() as usize / render_scale).max(1);
+
+    let width_f = w as f32;
+    let height_f = h as f32;
+
+    let num_pixels = w * h;
+    let colors: Vec<Color> = (0..num_pixels).into_par_iter().map(|idx| {
+        let i = idx % w;
+        let j = idx / w;
+        let (orig, dir) = camera.generate_ray((i as f32 + 0.5) / width_f, (j as f32 + 0.5) / height_f);
+        cast_ray(ctx, &orig, &dir)
+    }).collect();
+
+    for j in 0..h {
+        for i in 0..w {
+            let col = colors[j * w + i];
+            let dst_x = (i * render_scale) as u32;
+            let dst_y = (j * render_scale) as u32;
+            framebuffer.set_current_color(col);
+            for oy in 0..render_scale {
+                for ox in 0..render_scale {
+                    let px = dst_x + ox as u32;
+                    let py = dst_y + oy as u32;
+                    if px < framebuffer.width() && py < framebuffer.height() {
+                        framebuffer.set_pixel(px, py);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Acumulación progresiva a resolución completa: cada pasada suma una muestra
+// desplazada al azar dentro de cada pixel y `resolve` muestra el promedio.
+pub struct Accumulator {
+    width: usize,
+    height: usize,
+    sum: Vec<glm::Vec3>,
+    samples: u32,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Accumulator { width, height, sum: vec![glm::vec3(0.0, 0.0, 0.0); width * height], samples: 0 }
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn reset(&mut self) {
+        self.sum.fill(glm::vec3(0.0, 0.0, 0.0));
+        self.samples = 0;
+    }
+
+    pub fn accumulate(&mut self, ctx: &RenderContext, camera: &Camera) {
+        let w = self.width;
+        let width_f = self.width as f32;
+        let height_f = self.height as f32;
+        self.sum.par_iter_mut().enumerate().for_each_init(rand::rng, |rng, (idx, acc)| {
+            let x = (idx % w) as f32 + rng.random::<f32>();
+            let y = (idx / w) as f32 + rng.random::<f32>();
+            let (orig, dir) = camera.generate_ray(x / width_f, y / height_f);
+            *acc += cast_ray_linear(ctx, &orig, &dir);
+        });
+        self.samples += 1;
+    }
+
+    pub fn resolve(&self, framebuffer: &mut Framebuffer) {
+        if self.samples == 0 { return; }
+        let inv = 1.0 / self.samples as f32;
+        for (idx, acc) in self.sum.iter().enumerate() {
+            framebuffer.set_current_color(linear_to_color(&(acc * inv)));
+            framebuffer.set_pixel((idx % self.width) as u32, (idx / self.width) as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATER_IOR: f32 = 1.33;
+
+    #[test]
+    fn refract_total_internal_reflection() {
+        let n = glm::vec3(0.0, 1.0, 0.0);
+        // saliendo del agua con 60° de incidencia: supera el ángulo crítico (~48,8°)
+        let grazing = glm::normalize(&glm::vec3(60f32.to_radians().sin(), -60f32.to_radians().cos(), 0.0));
+        assert!(refract(&grazing, &n, WATER_IOR).is_none());
+        // con 30° sí sale, alejándose de la normal
+        let steep = glm::normalize(&glm::vec3(30f32.to_radians().sin(), -30f32.to_radians().cos(), 0.0));
+        let t = refract(&steep, &n, WATER_IOR).unwrap();
+        assert!((glm::length(&t) - 1.0).abs() < 1e-5);
+        assert!(t.x > steep.x && t.y < 0.0);
+        // de frente no se desvía
+        let straight = refract(&-n, &n, 1.0 / WATER_IOR).unwrap();
+        assert!(glm::length(&(straight + n)) < 1e-5);
+    }
+
+    #[test]
+    fn schlick_total_internal_reflection() {
+        let cos_60 = 60f32.to_radians().cos();
+        assert_eq!(schlick(cos_60, WATER_IOR, 1.0), 1.0);
+        // de frente: r0 en ambos sentidos
+        let r0 = ((WATER_IOR - 1.0) / (WATER_IOR + 1.0)).powi(2);
+        assert!((schlick(1.0, 1.0, WATER_IOR) - r0).abs() < 1e-6);
+        assert!((schlick(1.0, WATER_IOR, 1.0) - r0).abs() < 1e-6);
+        // al ras desde el aire casi todo se refleja
+        assert!(schlick(0.0, 1.0, WATER_IOR) > 0.99);
+    }
+
+    // Un bloque emisivo solo en la escena no debe hacerse sombra a sí mismo
+    #[test]
+    fn area_light_does_not_shadow_itself() {
+        let half = glm::vec3(0.5, 0.5, 0.5);
+        let emitter = Cube { center: glm::vec3(0.0, 0.0, 0.0), half_size: half, rot_y: 0.0, material: MaterialId::Pumpkin.material(), top_material: None, radius: glm::length(&half) };
+        let objects = vec![emitter];
+        let lights = crate::light::emissive_area_lights(&objects);
+        assert_eq!(lights.len(), 1);
+        let bvh = crate::bvh::build_bvh_with(&objects, crate::bvh::BuildOptions::default());
+        let ctx = RenderContext {
+            objects: &objects,
+            bvh: Some(&bvh),
+            textures: None,
+            lights: &lights,
+            ambient: 0.0,
+            sky: Sky::new(glm::vec3(0.0, 1.0, 0.0), 3.0),
+            environment: None,
+            ao: AoMode::Off,
+        };
+        // puntos de una pared vecina (x = 1.5) y del suelo (y = -1.5), de cara al bloque
+        let mut lit = 0;
+        let n = 40;
+        for i in 0..n {
+            for j in 0..n {
+                let a = -2.0 + 4.0 * (i as f32 + 0.5) / n as f32;
+                let b = -2.0 + 4.0 * (j as f32 + 0.5) / n as f32;
+                for (point, normal) in [(glm::vec3(1.5, a, b), glm::vec3(-1.0, 0.0, 0.0)), (glm::vec3(a, -1.5, b), glm::vec3(0.0, 1.0, 0.0))] {
+                    let (l, dist, _) = lights[0].illuminate(&point).unwrap();
+                    if shadow_factor(&ctx, &point, &normal, &l, light_shadow_distance(&lights[0], dist)) > 0.0 {
+                        lit += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(lit, 2 * n * n);
+    }
+}
@@ -0,0 +1,237 @@
+use nalgebra_glm as glm;
+
+use crate::light::Light;
+
+// Cielo y ciclo día/noche: una hora del día (0..24) fija la posición del sol, el cielo
+// (modelo de Preetham), el término ambiente y las estrellas. El render y el sombreado usan
+// el mismo valor.
+
+// Colores sRGB en [0, 1] del cielo nocturno (cénit, horizonte, suelo)
+const NIGHT: [[f32; 3]; 3] = [[0.02, 0.03, 0.09], [0.06, 0.08, 0.17], [0.02, 0.03, 0.06]];
+// Luz de luna: opuesta al sol, tenue y azulada
+const MOON_COLOR: [f32; 3] = [0.55, 0.65, 1.0];
+const MOON_INTENSITY: f32 = 0.18;
+// Fracción del ambiente que queda en plena noche
+const NIGHT_AMBIENT: f32 = 0.25;
+// Densidad de la grilla de estrellas y probabilidad de que una celda tenga estrella
+const STAR_GRID: f32 = 400.0;
+const STAR_DENSITY: f32 = 0.001;
+// Turbidez por defecto (1.7 = aire muy limpio, 10 = bruma densa)
+pub const DEFAULT_TURBIDITY: f32 = 3.0;
+// Escala de la luminancia de Preetham (kcd/m²) al rango del trazador
+const SKY_SCALE: f32 = 0.1;
+// Radio angular aparente del disco solar (agrandado para que se vea a baja resolución)
+const SUN_DISC_DEG: f32 = 0.8;
+const SUN_DISC_RADIANCE: f32 = 20.0;
+
+fn smoothstep(e0: f32, e1: f32, x: f32) -> f32 {
+    let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn srgb_to_linear(c: [f32; 3]) -> glm::Vec3 {
+    glm::vec3(c[0].powf(2.2), c[1].powf(2.2), c[2].powf(2.2))
+}
+
+// Normaliza una hora cualquiera a [0, 24)
+pub fn wrap_hours(hours: f32) -> f32 {
+    hours.rem_euclid(24.0)
+}
+
+// Dirección hacia el sol: sale por +x a las 6, culmina hacia -z al mediodía y se pone
+// por -x a las 18; de noche queda bajo el horizonte
+pub fn sun_direction(hours: f32) -> glm::Vec3 {
+    let angle = (wrap_hours(hours) - 6.0) / 12.0 * std::f32::consts::PI;
+    glm::normalize(&glm::vec3(angle.cos(), angle.sin() * 0.85, -angle.sin() * 0.5))
+}
+
+// Función de distribución de Perez: luminancia relativa según el ángulo cenital `theta`
+// de la dirección y su ángulo `gamma` con el sol
+fn perez(c: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    (1.0 + c[0] * (c[1] / cos_theta).exp()) * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos().powi(2))
+}
+
+// Cielo diurno analítico de Preetham (luminancia Y y cromaticidad x, y) para una posición
+// del sol y turbidez, con disco solar y bruma en el horizonte. Con el sol bajo el horizonte
+// se funde con un gradiente nocturno y aparecen las estrellas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sky {
+    // dirección hacia el sol (normalizada)
+    pub sun: glm::Vec3,
+    pub turbidity: f32,
+    // coeficientes de Perez para Y, x e y
+    coeffs: [[f32; 5]; 3],
+    // Y, x, y en el cénit divididos por F(0, theta_sol)
+    zenith: [f32; 3],
+    // color de la bruma y del disco solar (lineal)
+    haze: glm::Vec3,
+    sun_color: glm::Vec3,
+    // 0 = noche, 1 = día
+    day: f32,
+}
+
+impl Sky {
+    pub fn new(sun: glm::Vec3, turbidity: f32) -> Sky {
+        let t = turbidity.clamp(1.7, 10.0);
+        let sun = glm::normalize(&sun);
+        // Con el sol bajo el horizonte el modelo no vale: se evalúa con el sol apenas sobre él
+        let theta_s = sun.y.max(0.02).acos();
+        let coeffs = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta_s);
+        let y_z = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let t2 = t * t;
+        let x_z = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let yy_z = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+        let zenith = [
+            y_z / perez(&coeffs[0], 1.0, theta_s),
+            x_z / perez(&coeffs[1], 1.0, theta_s),
+            yy_z / perez(&coeffs[2], 1.0, theta_s),
+        ];
+        // El sol se enrojece al acercarse al horizonte (más aire que atravesar)
+        let low = 1.0 - smoothstep(0.0, 0.35, sun.y);
+        let sun_color = glm::mix(&glm::vec3(1.0, 0.95, 0.85), &glm::vec3(1.0, 0.45, 0.15), low);
+        let mut sky = Sky {
+            sun,
+            turbidity: t,
+            coeffs,
+            zenith,
+            haze: glm::vec3(0.0, 0.0, 0.0),
+            sun_color,
+            day: smoothstep(-0.2, 0.05, sun.y),
+        };
+        // La bruma toma el color del cielo en el horizonte del lado opuesto al sol, aclarado
+        let side = glm::normalize(&glm::vec3(-sun.x, 0.0, -sun.z + 1e-4));
+        sky.haze = glm::mix(&sky.preetham(&side), &sun_color, 0.3);
+        sky
+    }
+
+    pub fn at_time(hours: f32, turbidity: f32) -> Sky {
+        Sky::new(sun_direction(hours), turbidity)
+    }
+
+    // Radiancia del modelo de Preetham en una dirección sobre el horizonte (RGB lineal)
+    fn preetham(&self, dir: &glm::Vec3) -> glm::Vec3 {
+        let cos_theta = dir.y.max(0.01);
+        let gamma = glm::dot(dir, &self.sun).clamp(-1.0, 1.0).acos();
+        let big_y = self.zenith[0] * perez(&self.coeffs[0], cos_theta, gamma) * SKY_SCALE;
+        let x = self.zenith[1] * perez(&self.coeffs[1], cos_theta, gamma);
+        let y = (self.zenith[2] * perez(&self.coeffs[2], cos_theta, gamma)).max(1e-4);
+        // xyY -> XYZ -> sRGB lineal
+        let big_x = x * big_y / y;
+        let big_z = (1.0 - x - y) * big_y / y;
+        let rgb = glm::vec3(
+            3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z,
+        );
+        // exposición suave: la zona alrededor del sol no se satura de golpe
+        rgb.map(|c| 1.0 - (-c.max(0.0)).exp())
+    }
+
+    // Radiancia del cielo en la dirección `dir` (RGB lineal en [0, 1])
+    pub fn sample(&self, dir: &glm::Vec3) -> glm::Vec3 {
+        let night = if dir.y >= 0.0 {
+            glm::mix(&srgb_to_linear(NIGHT[1]), &srgb_to_linear(NIGHT[0]), dir.y.min(1.0))
+        } else {
+            glm::mix(&srgb_to_linear(NIGHT[1]), &srgb_to_linear(NIGHT[2]), (-dir.y).min(1.0))
+        };
+        let mut col = night;
+        if self.day > 0.0 {
+            let up = dir.y.max(0.0);
+            // bajo el horizonte se usa el cielo del horizonte en el mismo rumbo; mirando justo
+            // hacia abajo no hay rumbo y se toma uno cualquiera (normalizar daría NaN)
+            let sky_dir = glm::vec3(dir.x, up, dir.z);
+            let sky_dir = if glm::length(&sky_dir) > 1e-6 { sky_dir.normalize() } else { glm::vec3(1.0, 0.0, 0.0) };
+            let mut day = self.preetham(&sky_dir);
+            // Bruma: cerca del horizonte el cielo se aclara hacia el color de la bruma,
+            // más cuanto mayor es la turbidez
+            let haze_amount = ((self.turbidity - 1.7) / 8.3).clamp(0.0, 1.0) * 0.6 + 0.15;
+            day = glm::mix(&day, &self.haze, (-up * 8.0).exp() * haze_amount);
+            // Disco solar con un halo que se ensancha con la turbidez
+            let cos_gamma = glm::dot(dir, &self.sun);
+            let cos_disc = SUN_DISC_DEG.to_radians().cos();
+            if cos_gamma > cos_disc && dir.y > -0.01 {
+                day += self.sun_color * SUN_DISC_RADIANCE;
+            } else {
+                let glow = cos_gamma.max(0.0).powf(2000.0 / self.turbidity);
+                day += self.sun_color * (glow * 0.6);
+            }
+            if dir.y < 0.0 {
+                // bajo el horizonte: suelo lejano, más oscuro que la bruma
+                day = glm::mix(&day, &(self.haze * 0.35), smoothstep(0.0, 0.3, -dir.y));
+            }
+            col = glm::mix(&night, &day, self.day);
+        }
+        let stars = 1.0 - self.day;
+        if stars > 0.0 && dir.y > 0.0 {
+            // se apagan cerca del horizonte
+            col += glm::vec3(1.0, 1.0, 1.0) * (star(dir) * stars * smoothstep(0.0, 0.15, dir.y));
+        }
+        glm::clamp(&col, 0.0, 1.0)
+    }
+}
+
+// Brillo de la estrella en la celda de la grilla que contiene `dir` (casi siempre 0)
+fn star(dir: &glm::Vec3) -> f32 {
+    let cell = (dir * STAR_GRID).map(|c| c.floor() as i32);
+    let mut h = (cell.x as u32).wrapping_mul(0x8da6_b343)
+        ^ (cell.y as u32).wrapping_mul(0xd816_3841)
+        ^ (cell.z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    let chance = (h & 0xffff) as f32 / 65536.0;
+    if chance >= STAR_DENSITY { return 0.0; }
+    0.4 + 0.6 * (h >> 16) as f32 / 65536.0
+}
+
+// Luz direccional principal a esa hora: el sol (más cálido cerca del horizonte) o, de
+// noche, la luna. `base` es el sol de la escena y aporta color e intensidad.
+pub fn celestial_light(hours: f32, base: &Light) -> Light {
+    let (base_color, base_intensity) = match *base {
+        Light::Directional { color, intensity, .. } => (color, intensity),
+        _ => (glm::vec3(1.0, 1.0, 1.0), 1.0),
+    };
+    let sun = sun_direction(hours);
+    if sun.y > 0.0 {
+        let warm = glm::vec3(1.0, 0.55, 0.3);
+        let color = glm::mix(&warm.component_mul(&base_color), &base_color, smoothstep(0.0, 0.4, sun.y));
+        Light::Directional { direction: sun, color, intensity: base_intensity * smoothstep(-0.02, 0.12, sun.y) }
+    } else {
+        Light::Directional { direction: -sun, color: glm::vec3(MOON_COLOR[0], MOON_COLOR[1], MOON_COLOR[2]), intensity: MOON_INTENSITY * smoothstep(0.0, 0.15, -sun.y) }
+    }
+}
+
+// Factor del término ambiente a esa hora
+pub fn ambient_scale(hours: f32) -> f32 {
+    glm::mix_scalar(NIGHT_AMBIENT, 1.0, smoothstep(-0.15, 0.3, sun_direction(hours).y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_is_finite_in_every_direction() {
+        let sky = Sky::at_time(12.0, DEFAULT_TURBIDITY);
+        for dir in [glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 1.0, 0.0), glm::vec3(1.0, 0.0, 0.0), glm::normalize(&glm::vec3(1.0, -1e-7, 0.0))] {
+            let c = sky.sample(&dir);
+            assert!(c.iter().all(|v| v.is_finite()), "{:?} -> {:?}", dir, c);
+        }
+        // justo hacia abajo debe verse como casi hacia abajo, no un valor perdido por NaN
+        let down = sky.sample(&glm::vec3(0.0, -1.0, 0.0));
+        let almost_down = sky.sample(&glm::normalize(&glm::vec3(1e-3, -1.0, 0.0)));
+        assert!(glm::length(&(down - almost_down)) < 1e-3, "{:?} vs {:?}", down, almost_down);
+    }
+}
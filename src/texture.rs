@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use image::RgbaImage;
-use raylib::prelude::Color;
+use crate::color::Color;
 
 pub struct TextureManager {
     base: PathBuf,
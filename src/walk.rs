@@ -0,0 +1,163 @@
+use nalgebra_glm as glm;
+
+use crate::bvh::{BVH, overlapping_bvh};
+use crate::camera::Camera;
+use crate::cube::Cube;
+use crate::materials::MaterialId;
+
+// Modo caminar: el jugador es una cápsula vertical con gravedad que choca contra las cajas
+// de los cubos (consultadas con el BVH), sube sola escalones de hasta un bloque y salta.
+// La cámara va a la altura de los ojos; la posición se toma de la cámara en cada paso.
+
+// radio y alto de la cápsula, altura de los ojos sobre los pies (escala de Minecraft)
+const RADIUS: f32 = 0.3;
+const HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.62;
+const GRAVITY: f32 = 24.0;
+const JUMP_SPEED: f32 = 8.0;
+const MAX_FALL_SPEED: f32 = 40.0;
+// desnivel máximo que se sube caminando (un bloque con algo de margen)
+const STEP_HEIGHT: f32 = 1.05;
+pub const WALK_SPEED: f32 = 4.3;
+// paso de tiempo máximo por frame, para no atravesar bloques con tirones
+const MAX_DT: f32 = 0.05;
+// iteraciones para sacar la cápsula de los cubos tras cada movimiento
+const RESOLVE_ITERATIONS: usize = 4;
+
+// Geometría contra la que se camina
+pub struct Collider<'a> {
+    objects: &'a [Cube],
+    bvh: Option<&'a BVH>,
+}
+
+impl<'a> Collider<'a> {
+    pub fn new(objects: &'a [Cube], bvh: Option<&'a BVH>) -> Self {
+        Collider { objects, bvh }
+    }
+}
+
+// El agua no frena; todo lo demás es sólido
+fn blocks_movement(c: &Cube) -> bool {
+    c.material.id != MaterialId::Water
+}
+
+// Contactos acumulados al resolver un movimiento
+#[derive(Debug, Clone, Copy, Default)]
+struct Contacts {
+    ground: bool,
+    ceiling: bool,
+    wall: bool,
+}
+
+// Empuje mínimo que saca la cápsula (pies en `feet`) de la caja del cubo, o None si no se tocan
+fn push_out(feet: &glm::Vec3, c: &Cube) -> Option<glm::Vec3> {
+    let (min, max) = (c.center - c.half_size, c.center + c.half_size);
+    // segmento central de la cápsula, de y = a a y = b
+    let (a, b) = (feet.y + RADIUS, feet.y + HEIGHT - RADIUS);
+    let y = if b < min.y {
+        b
+    } else if a > max.y {
+        a
+    } else {
+        (a.max(min.y) + b.min(max.y)) * 0.5
+    };
+    let p = glm::vec3(feet.x, y, feet.z);
+    let d = p - glm::clamp_vec(&p, &min, &max);
+    let dist = glm::length(&d);
+    if dist >= RADIUS { return None; }
+    if dist > 1e-6 {
+        return Some(d / dist * (RADIUS - dist));
+    }
+    // el eje del segmento está dentro de la caja: salir por el lado más cercano
+    let candidates = [
+        glm::vec3(max.x - p.x + RADIUS, 0.0, 0.0),
+        glm::vec3(min.x - p.x - RADIUS, 0.0, 0.0),
+        glm::vec3(0.0, 0.0, max.z - p.z + RADIUS),
+        glm::vec3(0.0, 0.0, min.z - p.z - RADIUS),
+        glm::vec3(0.0, max.y - feet.y, 0.0),
+        glm::vec3(0.0, min.y - feet.y - HEIGHT, 0.0),
+    ];
+    candidates.into_iter().min_by(|u, v| glm::length(u).total_cmp(&glm::length(v)))
+}
+
+// Estado del jugador entre frames
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Walker {
+    vertical_speed: f32,
+    on_ground: bool,
+}
+
+impl Walker {
+    // Avanza `dt` segundos: `wish` es la velocidad horizontal pedida y `jump` salta si se está
+    // en el suelo. Mueve la cámara a los ojos de la nueva posición.
+    pub fn step(&mut self, world: &Collider, camera: &mut Camera, wish: &glm::Vec3, jump: bool, dt: f32) {
+        let dt = dt.min(MAX_DT);
+        let mut feet = camera.position - glm::vec3(0.0, EYE_HEIGHT, 0.0);
+        if jump && self.on_ground {
+            self.vertical_speed = JUMP_SPEED;
+        }
+        self.vertical_speed = (self.vertical_speed - GRAVITY * dt).max(-MAX_FALL_SPEED);
+
+        feet = self.move_horizontal(world, feet, &(glm::vec3(wish.x, 0.0, wish.z) * dt));
+        let (f, contacts) = move_by(world, feet, &glm::vec3(0.0, self.vertical_speed * dt, 0.0));
+        feet = f;
+        self.on_ground = contacts.ground && self.vertical_speed <= 0.0;
+        if self.on_ground || (contacts.ceiling && self.vertical_speed > 0.0) {
+            self.vertical_speed = 0.0;
+        }
+        camera.position = feet + glm::vec3(0.0, EYE_HEIGHT, 0.0);
+    }
+
+    // Movimiento horizontal; si una pared lo frena estando en el suelo, prueba subir un escalón
+    fn move_horizontal(&self, world: &Collider, feet: glm::Vec3, delta: &glm::Vec3) -> glm::Vec3 {
+        let (moved, contacts) = move_by(world, feet, delta);
+        let len = glm::length(delta);
+        if !(contacts.wall && self.on_ground) || len < 1e-6 { return moved; }
+        // sondeo: subir, avanzar al menos un radio (para apoyarse sobre el borde) y bajar
+        let probe = delta / len * len.max(RADIUS);
+        let (up, _) = move_by(world, feet, &glm::vec3(0.0, STEP_HEIGHT, 0.0));
+        let (ahead, _) = move_by(world, up, &probe);
+        let (down, landed) = move_by(world, ahead, &glm::vec3(0.0, feet.y - up.y, 0.0));
+        if !landed.ground || down.y <= feet.y + 1e-3 { return moved; }
+        // subir a la altura del escalón y avanzar solo lo pedido; vale si avanza más que sin subir
+        let (stepped, _) = move_by(world, glm::vec3(feet.x, down.y, feet.z), delta);
+        let progress = |p: &glm::Vec3| glm::dot(&(p - feet), delta);
+        if progress(&stepped) > progress(&moved) + 1e-4 { stepped } else { moved }
+    }
+}
+
+// Mueve los pies `delta` en pasos cortos (para no atravesar bloques) resolviendo los choques
+fn move_by(world: &Collider, mut feet: glm::Vec3, delta: &glm::Vec3) -> (glm::Vec3, Contacts) {
+    let steps = (glm::length(delta) / (RADIUS * 0.5)).ceil().max(1.0) as u32;
+    let mut contacts = Contacts::default();
+    for _ in 0..steps {
+        feet += delta / steps as f32;
+        feet = resolve(world, feet, &mut contacts);
+    }
+    (feet, contacts)
+}
+
+// Saca la cápsula de los cubos que toca, anotando de qué lado fue cada empuje
+fn resolve(world: &Collider, mut feet: glm::Vec3, contacts: &mut Contacts) -> glm::Vec3 {
+    let Some(bvh) = world.bvh else { return feet };
+    for _ in 0..RESOLVE_ITERATIONS {
+        let min = feet - glm::vec3(RADIUS, 0.0, RADIUS);
+        let max = feet + glm::vec3(RADIUS, HEIGHT, RADIUS);
+        let mut pushed = false;
+        for i in overlapping_bvh(bvh, world.objects, &min, &max, blocks_movement) {
+            let Some(push) = push_out(&feet, &world.objects[i]) else { continue };
+            let n = glm::normalize(&push);
+            if n.y > 0.7 {
+                contacts.ground = true;
+            } else if n.y < -0.7 {
+                contacts.ceiling = true;
+            } else {
+                contacts.wall = true;
+            }
+            feet += push;
+            pushed = true;
+        }
+        if !pushed { break; }
+    }
+    feet
+}